@@ -0,0 +1,5 @@
+; function to double a number
+        .global double
+double: LOADIMM r1, 2
+        MUL r0, r0, r1
+        RET
@@ -0,0 +1,10 @@
+; 5 factorial
+        LOADIMM r0, 5
+        LOADIMM r1, 1
+        LOADIMM r2, 0
+loop:   MUL r1, r1, r0
+        DECREMENT r0
+        COMPARE r0, r2
+        JUMPIFZERO loop
+        PUSH r1
+        HALT
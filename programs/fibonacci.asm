@@ -0,0 +1,17 @@
+; first 10 fibonacci numbers, pushed onto the stack
+        .equ COUNT, 10
+
+        LOADIMM r3, COUNT
+        LOADIMM r1, 0
+        LOADIMM r2, 1
+        LOADIMM r4, 0
+        PUSH r1
+        PUSH r2
+loop:   ADD r0, r1, r2
+        PUSH r0
+        COPY r1, r2
+        COPY r2, r0
+        DECREMENT r3
+        COMPARE r3, r4
+        JUMPIFZERO loop
+        HALT
@@ -0,0 +1,6 @@
+; function call and return, linked against double.asm
+        .extern double
+        .global main
+main:   LOADIMM r0, 33
+        CALL double
+        HALT
@@ -0,0 +1,8 @@
+; prints a greeting through the console syscalls and exits with code 0
+        LOADIMMW r0:r1, greeting
+        SYSCALL 4
+        LOADIMM r0, 0
+        SYSCALL 0
+
+.data
+greeting: .string "hello, world\n"
@@ -1,8 +1,168 @@
+mod assembly;
+mod expression;
+mod lexer;
+mod listing;
+mod macros;
+mod parser;
+
+use crate::config::MachineConfig;
+use crate::cpu::Data;
+use crate::instructions::Instruction;
+use crate::instructions::OperandKind;
 use crate::memory::Addressable;
+use crate::object::Object;
+use crate::object::Relocation;
+use crate::object::RelocationKind;
+use crate::object::Section;
+use crate::object::Target;
+use crate::symbols::SymbolTable;
+use assembly::Assembly;
+use assembly::Unit;
+use listing::Listing;
+use listing::ListingLine;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AssemblyErrorKind {
+    UnexpectedCharacter(char),
+    InvalidLiteral(String),
+    UnexpectedToken(String),
+    ExpectedOperand,
+    UnknownMnemonic(String),
+    OperandCount { mnemonic: &'static str, expected: usize, found: usize },
+    RegisterOutOfRange { index: usize, count: usize },
+    ExpectedRegister,
+    ExpectedPair,
+    UnpairedRegisters { high: usize, low: usize },
+    ExpectedMemory,
+    ExpectedStackSlot,
+    UnclosedBracket,
+    ExpectedValue,
+    ValueOutOfRange(i64),
+    ExpressionOutOfRange { expression: String, value: i64 },
+    ExpressionOverflow { expression: String },
+    DivisionByZero { expression: String },
+    InvalidRelocation { expression: String },
+    UnclosedParenthesis,
+    UnexpectedDirective(String),
+    UnterminatedBlock { directive: &'static str, line: usize },
+    MacroArguments { name: String, expected: usize, found: usize },
+    MacroRecursion { name: String, limit: usize },
+    MacroExpansion { name: String, inner: Box<AssemblyError> },
+    DuplicateSymbol { name: String, first_line: usize },
+    UndefinedSymbol(String),
+    UnknownDirective(String),
+    ExpectedText,
+    ExpectedSymbol,
+    ExpectedConstant(String),
+    OriginBackwards { origin: i64, location: i64 },
+    UninitializedSection,
+    ExportedNonLabel(String),
+    DoesNotFit { end: usize, size: usize },
+    UnknownOpcode(Data),
+}
+
+impl std::fmt::Display for AssemblyErrorKind {
+    fn fmt(&self, frmtr: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            | Self::UnexpectedCharacter(chr) => write!(frmtr, "unexpected character {chr:?}"),
+            | Self::InvalidLiteral(literal) => write!(frmtr, "invalid literal `{literal}`"),
+            | Self::UnexpectedToken(token) => write!(frmtr, "unexpected `{token}`"),
+            | Self::ExpectedOperand => write!(frmtr, "expected an operand"),
+            | Self::UnknownMnemonic(mnemonic) => write!(frmtr, "unknown mnemonic `{mnemonic}`"),
+            | Self::OperandCount { mnemonic, expected, found } => {
+                write!(frmtr, "`{mnemonic}` takes {expected} operand(s) but {found} were given")
+            }
+            | Self::RegisterOutOfRange { index, count } => {
+                write!(frmtr, "register r{index} does not exist, the processor has {count} registers")
+            }
+            | Self::ExpectedRegister => write!(frmtr, "expected a register"),
+            | Self::ExpectedPair => write!(frmtr, "expected a register pair such as `r2:r3`"),
+            | Self::UnpairedRegisters { high, low } => {
+                write!(frmtr, "r{high}:r{low} is not a pair, the low register must follow the high one")
+            }
+            | Self::ExpectedMemory => write!(frmtr, "expected a memory operand such as `[r2+4]`"),
+            | Self::ExpectedStackSlot => write!(frmtr, "expected a stack slot such as `[sp+1]`"),
+            | Self::UnclosedBracket => write!(frmtr, "expected `]`"),
+            | Self::ExpectedValue => write!(frmtr, "expected a value, found a register"),
+            | Self::ValueOutOfRange(value) => write!(frmtr, "value {value} does not fit in a data word"),
+            | Self::ExpressionOutOfRange { expression, value } => {
+                write!(frmtr, "`{expression}` evaluates to {value} which does not fit in a data word")
+            }
+            | Self::ExpressionOverflow { expression } => write!(frmtr, "`{expression}` overflows during evaluation"),
+            | Self::DivisionByZero { expression } => write!(frmtr, "`{expression}` divides by zero"),
+            | Self::InvalidRelocation { expression } => {
+                write!(frmtr, "`{expression}` combines addresses in a way that cannot be relocated")
+            }
+            | Self::UnclosedParenthesis => write!(frmtr, "expected `)`"),
+            | Self::UnexpectedDirective(name) => write!(frmtr, "`{name}` without a matching opening directive"),
+            | Self::UnterminatedBlock { directive, line } => {
+                write!(frmtr, "`{directive}` opened on line {line} is never closed")
+            }
+            | Self::MacroArguments { name, expected, found } => {
+                write!(frmtr, "macro `{name}` takes {expected} argument(s) but {found} were given")
+            }
+            | Self::MacroRecursion { name, limit } => {
+                write!(frmtr, "expanding `{name}` exceeds the nesting limit of {limit}")
+            }
+            | Self::MacroExpansion { name, inner } => write!(frmtr, "in expansion of `{name}`, {inner}"),
+            | Self::DuplicateSymbol { name, first_line } => {
+                write!(frmtr, "symbol `{name}` is already defined on line {first_line}")
+            }
+            | Self::UndefinedSymbol(name) => write!(frmtr, "symbol `{name}` is never defined"),
+            | Self::UnknownDirective(name) => write!(frmtr, "unknown directive `{name}`"),
+            | Self::ExpectedText => write!(frmtr, "expected a string literal"),
+            | Self::ExpectedSymbol => write!(frmtr, "expected a symbol name"),
+            | Self::ExpectedConstant(name) => {
+                write!(frmtr, "`{name}` must be a constant expression of symbols defined before this line")
+            }
+            | Self::OriginBackwards { origin, location } => {
+                write!(frmtr, "origin {origin} is behind the current location {location}")
+            }
+            | Self::UninitializedSection => write!(frmtr, "`.bss` can only reserve space with `.space` or `.align`"),
+            | Self::ExportedNonLabel(name) => write!(frmtr, "`{name}` must be a label to be exported"),
+            | Self::DoesNotFit { end, size } => {
+                write!(frmtr, "the program ends at {end:#x}, past the {size:#x} byte target")
+            }
+            | Self::UnknownOpcode(opcode) => write!(frmtr, "{opcode:#04x} is not an opcode"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssemblyError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: AssemblyErrorKind,
+}
+
+impl std::fmt::Display for AssemblyError {
+    fn fmt(&self, frmtr: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(frmtr, "line {}, column {}: {}", self.line, self.column, self.kind)
+    }
+}
+
+impl std::error::Error for AssemblyError {}
+
+/// one row of a raw program, the caller says whether it is an instruction or data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Row {
+    /// an opcode and its operands, the address operands are relocated to where the row lands
+    Instruction(Vec<Data>),
+    /// bytes placed as they are
+    Data(Vec<Data>),
+}
+
+/// assembles one module for the linker, leaving its relocations and external references unresolved
+pub fn assemble_object(source: &str, registers: usize) -> Result<Object, AssemblyError> {
+    Assembly::new(registers).assemble(source).map(|unit| unit.object)
+}
 
 #[derive(Debug)]
 pub struct ProgramAssembler<'d, Memory> {
     head: usize,
+    registers: usize,
+    symbols: SymbolTable,
+    listing: Option<Listing>,
     memory: &'d mut Memory,
 }
 
@@ -11,26 +171,142 @@ where
     Memory: Addressable<usize, Data = Data>,
 {
     pub fn build(target: &'d mut Memory) -> Self {
-        Self { head: Default::default(), memory: target }
+        Self {
+            head: Default::default(),
+            registers: MachineConfig::default().registers,
+            symbols: Default::default(),
+            listing: Default::default(),
+            memory: target,
+        }
+    }
+
+    pub fn with_registers(mut self, registers: usize) -> Self {
+        self.registers = registers;
+        self
+    }
+
+    pub fn with_listing(mut self) -> Self {
+        self.listing = Some(Listing::default());
+        self
+    }
+
+    pub fn listing(&self) -> Option<&Listing> {
+        self.listing.as_ref()
     }
 
-    pub fn assemble_program<Instructions>(&mut self, program: Vec<Instructions>)
-    where
-        Instructions: IntoIterator<Item = Data>,
-    {
-        for line in program {
-            self.assemble_instruction(line);
+    pub fn head(&self) -> usize {
+        self.head
+    }
+
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+}
+
+impl<Memory> ProgramAssembler<'_, Memory>
+where
+    Memory: Addressable<usize, Data = Data>,
+{
+    /// places raw rows after the previous program, each row counts as one line in errors and the listing
+    pub fn assemble_program(&mut self, program: Vec<Row>) -> Result<(), AssemblyError> {
+        let mut unit = Unit::default();
+        for (index, row) in program.into_iter().enumerate() {
+            let (line, offset) = (index + 1, unit.object.section(Section::Code).len());
+            let bytes = match row {
+                | Row::Data(bytes) => bytes,
+                | Row::Instruction(bytes) => {
+                    let Some(&opcode) = bytes.first()
+                    else {
+                        continue;
+                    };
+                    let Some(instruction) = Instruction::decode(opcode)
+                    else {
+                        return Err(AssemblyError { line, column: 1, kind: AssemblyErrorKind::UnknownOpcode(opcode) });
+                    };
+                    let operands = instruction.operand_kinds().iter().take(bytes.len() - 1);
+                    for (position, _) in operands.enumerate().filter(|(_, kind)| **kind == OperandKind::Address) {
+                        unit.object.relocations.push(Relocation {
+                            section: Section::Code,
+                            offset: offset + position + 1,
+                            kind: RelocationKind::Byte,
+                            target: Target::Section(Section::Code),
+                            addend: bytes[position + 1] as i64,
+                        });
+                        unit.positions.push((line, position + 2));
+                    }
+                    bytes
+                }
+            };
+            let source = String::new();
+            unit.listing.push(ListingLine {
+                line,
+                section: Section::Code,
+                address: offset,
+                bytes: bytes.clone(),
+                source,
+            });
+            unit.object.section_mut(Section::Code).extend(bytes);
         }
+
+        self.place(self.head, unit)
+    }
+
+    pub fn assemble_source(&mut self, source: &str) -> Result<(), AssemblyError> {
+        self.assemble_at(self.head, source)
     }
 
-    fn assemble_instruction<Instructions>(&mut self, instructions: Instructions)
-    where
-        Instructions: IntoIterator<Item = Data>,
-    {
-        for instruction in instructions {
-            *self.memory.write(self.head) = instruction;
+    pub fn assemble_at(&mut self, base: usize, source: &str) -> Result<(), AssemblyError> {
+        let unit = Assembly::new(self.registers).assemble(source)?;
+        self.place(base, unit)
+    }
+
+    /// lays the sections out back to back from `base`, external symbols must already be placed
+    fn place(&mut self, base: usize, unit: Unit) -> Result<(), AssemblyError> {
+        let Unit { mut object, positions, listing } = unit;
+        let mut bases = [base; 3];
+        for section in [Section::Data, Section::Bss] {
+            let previous = section as usize - 1;
+            bases[section as usize] = bases[previous].saturating_add(object.sections[previous].len());
+        }
+        let size = self.memory.size();
+        let line_end =
+            |line: &ListingLine| bases[line.section as usize].saturating_add(line.address + line.bytes.len());
+        if let Some(line) = listing.iter().find(|line| !line.bytes.is_empty() && line_end(line) > size) {
+            let end = base.saturating_add(object.sections.iter().map(Vec::len).sum());
+            let kind = AssemblyErrorKind::DoesNotFit { end, size };
+            return Err(AssemblyError { line: line.line, column: 1, kind });
+        }
+
+        for (relocation, (line, column)) in object.relocations.iter().zip(positions) {
+            let error = |kind| AssemblyError { line, column, kind };
+            let target = match &relocation.target {
+                | Target::Section(section) => bases[*section as usize],
+                | Target::External(name) => {
+                    self.symbols.address(name).ok_or_else(|| error(AssemblyErrorKind::UndefinedSymbol(name.clone())))?
+                }
+            };
+            let byte =
+                relocation.resolve(target).map_err(|address| error(AssemblyErrorKind::ValueOutOfRange(address)))?;
+            object.sections[relocation.section as usize][relocation.offset] = byte;
+        }
+        for symbol in &object.symbols {
+            self.symbols.insert(symbol.name.clone(), bases[symbol.section as usize] + symbol.offset);
+        }
+        if let Some(target) = &mut self.listing {
+            for mut line in listing {
+                let offset = line.address;
+                line.address += bases[line.section as usize];
+                line.bytes = object.section(line.section)[offset..offset + line.bytes.len()].to_vec();
+                target.lines.push(line);
+            }
+        }
+
+        self.head = base;
+        for byte in object.sections.into_iter().flatten() {
+            *self.memory.write(self.head) = byte;
             self.head += 1;
         }
+        Ok(())
     }
 }
 
@@ -40,10 +316,352 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn source_matches_rows() {
+        let mut expected = MemoryBlock::<u8>::new(16);
+        ProgramAssembler::build(&mut expected)
+            .assemble_program(vec![
+                Row::Instruction(vec![Instruction::LoadImm.into(), 3, 10]),
+                Row::Instruction(vec![Instruction::Add.into(), 0, 1, 2]),
+                Row::Instruction(vec![Instruction::JumpIfZero.into(), 0x10]),
+                Row::Instruction(vec![Instruction::Halt.into()]),
+            ])
+            .unwrap();
+
+        let mut mem = MemoryBlock::<u8>::new(16);
+        let source = "
+            ; comments and blank lines are skipped
+            LoadImm r3, 10
+            ADD r0, r1, r2
+            jumpifzero 0x10 ; hex literal
+            HALT
+        ";
+        ProgramAssembler::build(&mut mem).assemble_source(source).unwrap();
+        assert!(format!("{mem}") == format!("{expected}"));
+    }
+
+    #[test]
+    fn literal_forms() {
+        let mut mem = MemoryBlock::<u8>::new(8);
+        ProgramAssembler::build(&mut mem).assemble_source("LOADIMM r0, 'A'\nLOADIMM r1, 0b101").unwrap();
+        assert!(mem.read(2_usize) == b'A');
+        assert!(mem.read(5_usize) == 5);
+    }
+
+    #[test]
+    fn unknown_mnemonic() {
+        let mut mem = MemoryBlock::<u8>::new(8);
+        let error = ProgramAssembler::build(&mut mem).assemble_source("HALT\n  FOO r1").unwrap_err();
+        assert!(error == AssemblyError { line: 2, column: 3, kind: AssemblyErrorKind::UnknownMnemonic("FOO".into()) });
+    }
+
+    #[test]
+    fn operand_count() {
+        let mut mem = MemoryBlock::<u8>::new(8);
+        let error = ProgramAssembler::build(&mut mem).assemble_source("ADD r0, r1").unwrap_err();
+        assert!(matches!(error.kind, AssemblyErrorKind::OperandCount { expected: 3, found: 2, .. }));
+    }
+
+    #[test]
+    fn register_range() {
+        let mut mem = MemoryBlock::<u8>::new(8);
+        let error = ProgramAssembler::build(&mut mem).assemble_source("PUSH r8").unwrap_err();
+        assert!(error.column == 6);
+        assert!(error.kind == AssemblyErrorKind::RegisterOutOfRange { index: 8, count: 8 });
+        assert!(mem.read(0_usize) == 0);
+    }
+
+    #[test]
+    fn memory_operand_errors() {
+        let error = |source: &str| {
+            let mut mem = MemoryBlock::<u8>::new(8);
+            ProgramAssembler::build(&mut mem).assemble_source(source).unwrap_err().kind
+        };
+        assert!(error("LOADIND r0, r1") == AssemblyErrorKind::ExpectedMemory);
+        assert!(error("LOADPAIR r0, [r1]") == AssemblyErrorKind::ExpectedPair);
+        assert!(error("LOADPAIR r0, [r7:r8]") == AssemblyErrorKind::RegisterOutOfRange { index: 8, count: 8 });
+        assert!(error("STORE [r1], r0") == AssemblyErrorKind::ExpectedValue);
+        assert!(error("LOADSP r0, [r1]") == AssemblyErrorKind::ExpectedStackSlot);
+        assert!(error("LOADIND r0, [sp]") == AssemblyErrorKind::ExpectedRegister);
+    }
+
     #[test]
     fn label() {
-        let mut mem = MemoryBlock::<10, u8>::default();
-        let writer = ProgramAssembler::build(&mut mem);
-        todo!("need to implement a way to store symbolic labels for jumps {:?}", writer);
+        let mut mem = MemoryBlock::<u8>::new(10);
+        let mut writer = ProgramAssembler::build(&mut mem);
+        writer.assemble_source("start: JUMP end\n PUSH r0\nend:\n JUMP start").unwrap();
+        assert!(mem.read(1_usize) == 4);
+        assert!(mem.read(5_usize) == 0);
+    }
+
+    #[test]
+    fn label_after_head() {
+        let mut mem = MemoryBlock::<u8>::new(10);
+        let mut writer = ProgramAssembler::build(&mut mem);
+        writer.assemble_source("HALT").unwrap();
+        writer.assemble_source("here: JUMP here").unwrap();
+        assert!(mem.read(2_usize) == 1);
+    }
+
+    #[test]
+    fn relocated_source() {
+        let source = "top: JUMP top\n LOADIMM r0, top\n LOADIMM r1, 7";
+        let mut mem = MemoryBlock::<u8>::new(16);
+        let mut writer = ProgramAssembler::build(&mut mem);
+        writer.assemble_at(0, source).unwrap();
+        writer.assemble_at(8, source).unwrap();
+        assert!(mem.read(1_usize) == 0 && mem.read(4_usize) == 0);
+        assert!(mem.read(9_usize) == 8 && mem.read(12_usize) == 8);
+        assert!(mem.read(15_usize) == 7);
+    }
+
+    #[test]
+    fn relocated_rows() {
+        let mut mem = MemoryBlock::<u8>::new(16);
+        let mut writer = ProgramAssembler::build(&mut mem);
+        writer.assemble_program(vec![Row::Instruction(vec![Instruction::Halt.into()])]).unwrap();
+        writer
+            .assemble_program(vec![
+                Row::Instruction(vec![Instruction::LoadImm.into(), 0, 3]),
+                Row::Instruction(vec![Instruction::Jump.into(), 3]),
+            ])
+            .unwrap();
+        writer.assemble_program(vec![Row::Data(vec![0xFF, 7]), Row::Data(vec![Instruction::Jump.into(), 20])]).unwrap();
+        let error = writer.assemble_program(vec![Row::Instruction(vec![Instruction::Jump.into(), 0xFF])]).unwrap_err();
+        assert!(error == AssemblyError { line: 1, column: 2, kind: AssemblyErrorKind::ValueOutOfRange(0x109) });
+        let error = writer.assemble_program(vec![Row::Data(vec![]), Row::Instruction(vec![0xFF])]).unwrap_err();
+        assert!(error == AssemblyError { line: 2, column: 1, kind: AssemblyErrorKind::UnknownOpcode(0xFF) });
+        assert!(mem.read(3_usize) == 3);
+        assert!(mem.read(5_usize) == 4);
+        assert!(mem.read(6_usize) == 0xFF && mem.read(7_usize) == 7);
+        assert!(mem.read(8_usize) == Instruction::Jump.into() && mem.read(9_usize) == 20);
+    }
+
+    #[test]
+    fn relocation_out_of_range() {
+        let mut mem = MemoryBlock::<u8>::new(512);
+        let error = ProgramAssembler::build(&mut mem).assemble_at(300, "\nend: JUMP end").unwrap_err();
+        assert!(error == AssemblyError { line: 2, column: 11, kind: AssemblyErrorKind::ValueOutOfRange(300) });
+    }
+
+    #[test]
+    fn placed_past_the_target() {
+        let mut mem = MemoryBlock::<u8>::new(16);
+        let error = ProgramAssembler::build(&mut mem).assemble_at(14, "HALT\nLOADIMM r0, 1").unwrap_err();
+        assert!(
+            error == AssemblyError { line: 2, column: 1, kind: AssemblyErrorKind::DoesNotFit { end: 18, size: 16 } }
+        );
+        assert!(mem.read(14_usize) == 0);
+    }
+
+    #[test]
+    fn data_directives() {
+        let source = "
+            .equ COUNT, 3
+                JUMP start
+            greeting:
+                .string \"hi\"
+                .fill COUNT, 0xAA
+                .byte 1, greeting, 'z'
+                .align 4
+            start:
+                LOADIMM r0, COUNT
+        ";
+        let mut mem = MemoryBlock::<u8>::new(32);
+        ProgramAssembler::build(&mut mem).assemble_at(4, source).unwrap();
+        let expected = [b'h', b'i', 0, 0xAA, 0xAA, 0xAA, 1, 6, b'z', 0];
+        assert!((0..expected.len()).all(|index| mem.read(6 + index) == expected[index]));
+        assert!(mem.read(5_usize) == 16);
+        assert!(mem.read(18_usize) == 3);
+    }
+
+    #[test]
+    fn origin() {
+        let mut mem = MemoryBlock::<u8>::new(32);
+        ProgramAssembler::build(&mut mem).assemble_source(".org 0x10\nentry: JUMP entry").unwrap();
+        assert!(mem.read(0x11_usize) == 0x10);
+
+        let source = "HALT\n.org 4\nhere: .byte 1\n.data\n.org 2\nthere: .byte here";
+        let mut writer = ProgramAssembler::build(&mut mem);
+        writer.assemble_at(0x10, source).unwrap();
+        assert!(writer.symbols().address("here") == Some(0x14) && writer.symbols().address("there") == Some(0x17));
+        assert!(mem.read(0x14_usize) == 1 && mem.read(0x17_usize) == 0x14);
+
+        let error = ProgramAssembler::build(&mut mem).assemble_source("HALT\nHALT\n.org 1").unwrap_err();
+        assert!(error.kind == AssemblyErrorKind::OriginBackwards { origin: 1, location: 2 });
+
+        let past_end = |source: &str| assemble_object(source, 8).unwrap_err().kind;
+        assert!(past_end(".org 0x7fffffffffff") == AssemblyErrorKind::ValueOutOfRange(0x7FFF_FFFF_FFFF));
+        assert!(past_end("HALT\n.space 0x10000") == AssemblyErrorKind::ValueOutOfRange(0x10000));
+        assert!(past_end(".fill 0x10001, 0") == AssemblyErrorKind::ValueOutOfRange(0x10001));
+        assert!(past_end("HALT\n.align 0x7fffffffffffffff") == AssemblyErrorKind::ValueOutOfRange(i64::MAX));
+        assert!(past_end(".align 0") == AssemblyErrorKind::ValueOutOfRange(0));
+        assert!(assemble_object(".bss\n.space 0x10000", 8).is_ok());
+    }
+
+    #[test]
+    fn constant_required() {
+        let mut mem = MemoryBlock::<u8>::new(32);
+        let error = ProgramAssembler::build(&mut mem).assemble_source(".fill later, 0\n.equ later, 2").unwrap_err();
+        assert!(
+            error == AssemblyError { line: 1, column: 7, kind: AssemblyErrorKind::ExpectedConstant("later".into()) }
+        );
+    }
+
+    #[test]
+    fn operand_expressions() {
+        let source = "
+            .equ SIZE, 3
+            start:  LOADIMM r0, (SIZE*2)|1
+                    LOADIMM r1, end-start
+                    LOADIMM r2, -1
+            end:    .byte <end, >end, end-start+1
+        ";
+        let mut mem = MemoryBlock::<u8>::new(512);
+        ProgramAssembler::build(&mut mem).assemble_at(0x1F0, source).unwrap();
+        let expected = [7, 9, 0xFF, 0xF9, 0x01, 0xFA];
+        assert!(
+            [0x1F2, 0x1F5, 0x1F8, 0x1F9, 0x1FA].iter().zip(expected).all(|(at, byte)| mem.read(*at as usize) == byte)
+        );
+
+        let error =
+            ProgramAssembler::build(&mut mem).assemble_source("\n LOADIMM r0, SIZE * 100\n.equ SIZE, 3").unwrap_err();
+        let kind = AssemblyErrorKind::ExpressionOutOfRange { expression: "SIZE*100".into(), value: 300 };
+        assert!(error == AssemblyError { line: 2, column: 14, kind });
+    }
+
+    #[test]
+    fn macro_expansion() {
+        let source = "
+            .macro PUSH2 a, b
+                PUSH a
+                PUSH b
+            .endm
+            .macro COUNTDOWN reg
+            @again: DECREMENT reg
+                PUSH2 reg, reg
+                COMPARE reg, r7
+                JUMPIFZERO @again
+            .endm
+            COUNTDOWN r1
+            COUNTDOWN r2
+        ";
+        let mut mem = MemoryBlock::<u8>::new(32);
+        ProgramAssembler::build(&mut mem).assemble_source(source).unwrap();
+        let expected = [
+            vec![Instruction::Decrement.into(), 1],
+            vec![Instruction::Push.into(), 1],
+            vec![Instruction::Push.into(), 1],
+            vec![Instruction::Compare.into(), 1, 7],
+            vec![Instruction::JumpIfZero.into(), 0],
+        ]
+        .concat();
+        assert!((0..expected.len()).all(|index| mem.read(index) == expected[index]));
+        assert!(mem.read(12_usize) == 2 && mem.read(21_usize) == 11);
+    }
+
+    #[test]
+    fn conditional_assembly() {
+        let source = "
+            .equ DEBUG, 0
+            .if DEBUG
+                PUSH r0
+                .if 1
+                    garbage that never assembles
+                .endif
+            .else
+                HALT
+            .endif
+            .if DEBUG | 1
+                RET
+            .endif
+        ";
+        let mut mem = MemoryBlock::<u8>::new(8);
+        ProgramAssembler::build(&mut mem).assemble_source(source).unwrap();
+        assert!(mem.read(0_usize) == Instruction::Halt.into());
+        assert!(mem.read(1_usize) == Instruction::Ret.into());
+
+        let error = ProgramAssembler::build(&mut mem).assemble_source(".if 1\nHALT").unwrap_err();
+        assert!(error.kind == AssemblyErrorKind::UnterminatedBlock { directive: ".if", line: 1 });
+    }
+
+    #[test]
+    fn macro_errors() {
+        let mut mem = MemoryBlock::<u8>::new(8);
+        let source = ".macro LOOP\n LOOP\n.endm\n LOOP";
+        let error = ProgramAssembler::build(&mut mem).assemble_source(source).unwrap_err();
+        assert!(error.line == 4);
+        assert!(matches!(error.kind, AssemblyErrorKind::MacroExpansion { .. }));
+        assert!(error.to_string().contains("exceeds the nesting limit of 16"));
+
+        let source = ".macro ONE x\n PUSH x\n.endm\n ONE r1, r2";
+        let error = ProgramAssembler::build(&mut mem).assemble_source(source).unwrap_err();
+        assert!(error.kind == AssemblyErrorKind::MacroArguments { name: "ONE".into(), expected: 1, found: 2 });
+
+        let source = ".macro ONE x\n PUSH x, x\n.endm\n ONE r1";
+        let error = ProgramAssembler::build(&mut mem).assemble_source(source).unwrap_err();
+        let AssemblyErrorKind::MacroExpansion { inner, .. } = error.kind
+        else {
+            panic!("expected the error to point into the macro body");
+        };
+        assert!(inner.line == 2 && matches!(inner.kind, AssemblyErrorKind::OperandCount { .. }));
+    }
+
+    #[test]
+    fn sections() {
+        let source = ".data\nvalue: .byte 9\n.code\nstart: LOADIMM r0, value\n.bss\nbuffer: .space 2";
+        let mut mem = MemoryBlock::<u8>::new(16);
+        let mut writer = ProgramAssembler::build(&mut mem);
+        writer.assemble_at(4, source).unwrap();
+        assert!(writer.head() == 10 && writer.symbols().address("buffer") == Some(8));
+        assert!(mem.read(6_usize) == 7 && mem.read(7_usize) == 9);
+
+        let error = ProgramAssembler::build(&mut mem).assemble_source(".bss\n.byte 1").unwrap_err();
+        assert!(error == AssemblyError { line: 2, column: 7, kind: AssemblyErrorKind::UninitializedSection });
+    }
+
+    #[test]
+    fn external_symbols() {
+        let mut mem = MemoryBlock::<u8>::new(16);
+        let mut writer = ProgramAssembler::build(&mut mem);
+        writer.assemble_source(".global entry\nHALT\nentry: HALT").unwrap();
+        writer.assemble_source(".extern entry\nJUMP entry").unwrap();
+
+        let error = writer.assemble_source(".extern missing\nJUMP missing").unwrap_err();
+        assert!(error.kind == AssemblyErrorKind::UndefinedSymbol("missing".into()));
+        let error = writer.assemble_source(".equ SIZE, 2\n.global SIZE").unwrap_err();
+        assert!(
+            error == AssemblyError { line: 2, column: 9, kind: AssemblyErrorKind::ExportedNonLabel("SIZE".into()) }
+        );
+        assert!(mem.read(3_usize) == 1);
+    }
+
+    #[test]
+    fn listing_lines() {
+        let mut mem = MemoryBlock::<u8>::new(16);
+        let mut writer = ProgramAssembler::build(&mut mem).with_listing();
+        writer.assemble_at(4, "; entry\nstart: LOADIMM r1, start\n.string \"ok\"").unwrap();
+        let lines = &writer.listing().unwrap().lines;
+        assert!(lines[0].bytes.is_empty());
+        assert!(lines[1].address == 4 && lines[1].bytes == [Instruction::LoadImm.into(), 1, 4]);
+        assert!(lines[2].address == 7 && lines[2].bytes == [b'o', b'k', 0]);
+        assert!(ProgramAssembler::build(&mut mem).listing().is_none());
+    }
+
+    #[test]
+    fn undefined_label() {
+        let mut mem = MemoryBlock::<u8>::new(10);
+        let error = ProgramAssembler::build(&mut mem).assemble_source("JUMP nowhere").unwrap_err();
+        assert!(
+            error == AssemblyError { line: 1, column: 6, kind: AssemblyErrorKind::UndefinedSymbol("nowhere".into()) }
+        );
+    }
+
+    #[test]
+    fn duplicate_label() {
+        let mut mem = MemoryBlock::<u8>::new(10);
+        let error = ProgramAssembler::build(&mut mem).assemble_source("top: HALT\ntop: HALT").unwrap_err();
+        assert!(error.line == 2);
+        assert!(error.kind == AssemblyErrorKind::DuplicateSymbol { name: "top".into(), first_line: 1 });
     }
 }
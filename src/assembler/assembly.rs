@@ -0,0 +1,612 @@
+use crate::assembler::AssemblyError;
+use crate::assembler::AssemblyErrorKind;
+use crate::assembler::expression::Evaluated;
+use crate::assembler::expression::Expression;
+use crate::assembler::expression::UnaryOperator;
+use crate::assembler::lexer;
+use crate::assembler::lexer::Lexeme;
+use crate::assembler::lexer::Token;
+use crate::assembler::listing::ListingLine;
+use crate::assembler::macros;
+use crate::assembler::macros::EXPANSION_LIMIT;
+use crate::assembler::macros::Macro;
+use crate::assembler::parser;
+use crate::assembler::parser::Argument;
+use crate::assembler::parser::Operand;
+use crate::assembler::parser::Statement;
+use crate::cpu::ADDRESS_SPACE;
+use crate::cpu::Data;
+use crate::instructions::Instruction;
+use crate::instructions::OperandKind;
+use crate::object::Object;
+use crate::object::ObjectSymbol;
+use crate::object::Relocation;
+use crate::object::Section;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolKind {
+    Label(Section),
+    Constant,
+    External,
+}
+
+#[derive(Debug)]
+pub struct Definition {
+    pub value: i64,
+    pub kind: SymbolKind,
+    pub line: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct Unit {
+    pub object: Object,
+    /// source line and column of each object relocation, for errors raised once the unit is placed
+    pub positions: Vec<(usize, usize)>,
+    pub listing: Vec<ListingLine>,
+}
+
+#[derive(Debug)]
+struct Fixup {
+    section: Section,
+    offset: usize,
+    expression: Expression,
+    line: usize,
+    column: usize,
+}
+
+#[derive(Debug)]
+struct Conditional {
+    enclosing: bool,
+    taken: bool,
+    otherwise: bool,
+    line: usize,
+}
+
+impl Conditional {
+    fn active(&self) -> bool {
+        self.enclosing && self.taken != self.otherwise
+    }
+}
+
+#[derive(Debug)]
+struct Recording {
+    definition: Macro,
+    nesting: usize,
+}
+
+#[derive(Debug)]
+pub struct Assembly {
+    registers: usize,
+    sections: [Vec<Data>; 3],
+    section: Section,
+    symbols: HashMap<String, Definition>,
+    globals: Vec<(String, usize, usize)>,
+    fixups: Vec<Fixup>,
+    macros: HashMap<String, Macro>,
+    conditionals: Vec<Conditional>,
+    recording: Option<Recording>,
+    expansions: usize,
+    listing: Vec<ListingLine>,
+}
+
+impl Assembly {
+    pub fn new(registers: usize) -> Self {
+        Self {
+            registers,
+            sections: Default::default(),
+            section: Section::Code,
+            symbols: HashMap::new(),
+            globals: Vec::new(),
+            fixups: Vec::new(),
+            macros: HashMap::new(),
+            conditionals: Vec::new(),
+            recording: None,
+            expansions: 0,
+            listing: Vec::new(),
+        }
+    }
+
+    pub fn assemble(mut self, source: &str) -> Result<Unit, AssemblyError> {
+        for (index, text) in source.lines().enumerate() {
+            let line = index + 1;
+            let (section, offset) = (self.section, self.location());
+            match lexer::tokenize(line, text) {
+                | Ok(lexemes) => self.line(line, lexemes, 0)?,
+                | Err(error) if self.active() || self.recording.is_some() => return Err(error),
+                | Err(_) => {}
+            }
+            let length = if self.section == section { self.location() - offset } else { 0 };
+            let bytes = vec![Default::default(); length];
+            self.listing.push(ListingLine { line, section, address: offset, bytes, source: text.into() });
+        }
+
+        if let Some(recording) = &self.recording {
+            let kind = AssemblyErrorKind::UnterminatedBlock { directive: ".macro", line: recording.definition.line };
+            return Err(AssemblyError { line: source.lines().count(), column: 1, kind });
+        }
+        if let Some(conditional) = self.conditionals.last() {
+            let kind = AssemblyErrorKind::UnterminatedBlock { directive: ".if", line: conditional.line };
+            return Err(AssemblyError { line: source.lines().count(), column: 1, kind });
+        }
+        self.resolve()
+    }
+
+    fn location(&self) -> usize {
+        self.sections[self.section as usize].len()
+    }
+
+    fn image(&mut self) -> &mut Vec<Data> {
+        &mut self.sections[self.section as usize]
+    }
+
+    /// bss only reserves space, anything that would give it contents is rejected
+    fn initialized(&self, line: usize, column: usize) -> Result<(), AssemblyError> {
+        match self.section {
+            | Section::Bss => Err(AssemblyError { line, column, kind: AssemblyErrorKind::UninitializedSection }),
+            | _ => Ok(()),
+        }
+    }
+
+    fn active(&self) -> bool {
+        self.conditionals.last().is_none_or(Conditional::active)
+    }
+
+    fn line(&mut self, line: usize, lexemes: Vec<Lexeme>, depth: usize) -> Result<(), AssemblyError> {
+        let (index, keyword) = match keyword(&lexemes) {
+            | Some((index, keyword)) => (index, keyword.to_ascii_lowercase()),
+            | None => (lexemes.len(), String::new()),
+        };
+
+        if let Some(recording) = &mut self.recording {
+            match keyword.as_str() {
+                | ".macro" => recording.nesting += 1,
+                | ".endm" if recording.nesting == 0 => {
+                    let definition = self.recording.take().map(|recording| recording.definition);
+                    return definition.map_or(Ok(()), |definition| self.define_macro(definition));
+                }
+                | ".endm" => recording.nesting -= 1,
+                | _ => {}
+            }
+            recording.definition.body.push((line, lexemes));
+            return Ok(());
+        }
+
+        let column = lexemes.get(index).map_or(1, |lexeme| lexeme.column);
+        let error = |kind| AssemblyError { line, column, kind };
+        match keyword.as_str() {
+            | ".if" => {
+                let enclosing = self.active();
+                let taken = enclosing && self.condition(line, &lexemes[index..])?;
+                self.conditionals.push(Conditional { enclosing, taken, otherwise: false, line });
+                return Ok(());
+            }
+            | ".else" => {
+                match self.conditionals.last_mut() {
+                    | Some(conditional) if !conditional.otherwise => conditional.otherwise = true,
+                    | _ => return Err(error(AssemblyErrorKind::UnexpectedDirective(keyword))),
+                }
+                return Ok(());
+            }
+            | ".endif" => {
+                self.conditionals.pop().ok_or_else(|| error(AssemblyErrorKind::UnexpectedDirective(keyword)))?;
+                return Ok(());
+            }
+            | _ if !self.active() => return Ok(()),
+            | ".macro" => {
+                self.statements(line, &lexemes[..index])?;
+                let definition = Macro::define(line, column + keyword.len(), &lexemes[index + 1..])?;
+                self.recording = Some(Recording { definition, nesting: 0 });
+                return Ok(());
+            }
+            | ".endm" => return Err(error(AssemblyErrorKind::UnexpectedDirective(keyword))),
+            | _ => {}
+        }
+
+        let name = keyword.to_ascii_uppercase();
+        match self.macros.get(&name).cloned() {
+            | Some(definition) => {
+                self.statements(line, &lexemes[..index])?;
+                self.expand(line, column, &definition, &lexemes[index + 1..], depth)
+            }
+            | None => self.statements(line, &lexemes),
+        }
+    }
+
+    fn statements(&mut self, line: usize, lexemes: &[Lexeme]) -> Result<(), AssemblyError> {
+        for statement in parser::parse_line(line, lexemes)? {
+            match statement {
+                | Statement::Label { name, column } => {
+                    self.define(&name, self.location() as i64, SymbolKind::Label(self.section), line, column)?;
+                }
+                | Statement::Instruction { mnemonic, column, arguments } => {
+                    self.instruction(line, &mnemonic, column, &arguments)?;
+                }
+                | Statement::Directive { name, column, arguments } => {
+                    self.directive(line, &name, column, &arguments)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn define_macro(&mut self, definition: Macro) -> Result<(), AssemblyError> {
+        let name = definition.name.to_ascii_uppercase();
+        if let Some(previous) = self.macros.get(&name) {
+            let kind = AssemblyErrorKind::DuplicateSymbol { name: definition.name.clone(), first_line: previous.line };
+            return Err(AssemblyError { line: definition.line, column: 1, kind });
+        }
+        self.macros.insert(name, definition);
+        Ok(())
+    }
+
+    fn expand(
+        &mut self,
+        line: usize,
+        column: usize,
+        definition: &Macro,
+        lexemes: &[Lexeme],
+        depth: usize,
+    ) -> Result<(), AssemblyError> {
+        let error = |kind| AssemblyError { line, column, kind };
+        if depth >= EXPANSION_LIMIT {
+            return Err(error(AssemblyErrorKind::MacroRecursion {
+                name: definition.name.clone(),
+                limit: EXPANSION_LIMIT,
+            }));
+        }
+
+        let arguments = macros::split_arguments(lexemes);
+        if arguments.len() != definition.parameters.len() || arguments.iter().any(|argument| argument.is_empty()) {
+            let kind = AssemblyErrorKind::MacroArguments {
+                name: definition.name.clone(),
+                expected: definition.parameters.len(),
+                found: arguments.len(),
+            };
+            return Err(error(kind));
+        }
+
+        self.expansions += 1;
+        let conditionals = self.conditionals.len();
+        for (body_line, body) in definition.expand(&arguments, self.expansions) {
+            self.line(body_line, body, depth + 1).map_err(|inner| {
+                error(AssemblyErrorKind::MacroExpansion { name: definition.name.clone(), inner: Box::new(inner) })
+            })?;
+        }
+        if self.conditionals.len() != conditionals {
+            return Err(error(AssemblyErrorKind::UnterminatedBlock { directive: ".if", line: definition.line }));
+        }
+        Ok(())
+    }
+
+    fn condition(&self, line: usize, lexemes: &[Lexeme]) -> Result<bool, AssemblyError> {
+        let Some(Statement::Directive { column, arguments, .. }) = parser::parse_line(line, lexemes)?.pop()
+        else {
+            return Ok(false);
+        };
+        arity(line, column, ".if", &arguments, 1)?;
+        Ok(self.constant(line, &arguments[0])? != 0)
+    }
+
+    fn define(
+        &mut self,
+        name: &str,
+        value: i64,
+        kind: SymbolKind,
+        line: usize,
+        column: usize,
+    ) -> Result<(), AssemblyError> {
+        if let Some(previous) = self.symbols.get(name) {
+            let kind = AssemblyErrorKind::DuplicateSymbol { name: name.into(), first_line: previous.line };
+            return Err(AssemblyError { line, column, kind });
+        }
+        self.symbols.insert(name.into(), Definition { value, kind, line });
+        Ok(())
+    }
+
+    fn resolve(mut self) -> Result<Unit, AssemblyError> {
+        let mut object = Object::default();
+        let mut positions = Vec::new();
+        for Fixup { section, offset, expression, line, column } in self.fixups {
+            let error = |kind| AssemblyError { line, column, kind };
+            let Evaluated { value, relocation } = expression.evaluate(&self.symbols).map_err(error)?;
+            match relocation {
+                | Some((kind, target)) => {
+                    object.relocations.push(Relocation { section, offset, kind, target, addend: value });
+                    positions.push((line, column));
+                }
+                | None => {
+                    self.sections[section as usize][offset] = data_word(value).ok_or_else(|| {
+                        error(AssemblyErrorKind::ExpressionOutOfRange { expression: expression.to_string(), value })
+                    })?;
+                }
+            }
+        }
+
+        for (name, line, column) in &self.globals {
+            let error = |kind| AssemblyError { line: *line, column: *column, kind };
+            match self.symbols.get(name).map(|definition| definition.kind) {
+                | Some(SymbolKind::Label(_)) => {}
+                | Some(_) => return Err(error(AssemblyErrorKind::ExportedNonLabel(name.clone()))),
+                | None => return Err(error(AssemblyErrorKind::UndefinedSymbol(name.clone()))),
+            }
+        }
+        for (name, definition) in self.symbols {
+            match definition.kind {
+                | SymbolKind::Label(section) => object.symbols.push(ObjectSymbol {
+                    global: self.globals.iter().any(|(global, ..)| *global == name),
+                    name,
+                    section,
+                    offset: definition.value as usize,
+                }),
+                | SymbolKind::External => object.externals.push(name),
+                | SymbolKind::Constant => {}
+            }
+        }
+        object
+            .symbols
+            .sort_by(|lhs, rhs| (lhs.section, lhs.offset, &lhs.name).cmp(&(rhs.section, rhs.offset, &rhs.name)));
+        object.externals.sort();
+        object.sections = self.sections;
+        Ok(Unit { object, positions, listing: self.listing })
+    }
+
+    fn instruction(
+        &mut self,
+        line: usize,
+        mnemonic: &str,
+        column: usize,
+        arguments: &[Argument],
+    ) -> Result<(), AssemblyError> {
+        let error = |column, kind| AssemblyError { line, column, kind };
+        let instruction = Instruction::from_mnemonic(mnemonic)
+            .ok_or_else(|| error(column, AssemblyErrorKind::UnknownMnemonic(mnemonic.into())))?;
+        let kinds = instruction.operand_kinds();
+        let written = kinds.iter().filter(|kind| !matches!(kind, OperandKind::Offset | OperandKind::Wide)).count();
+        arity(line, column, instruction.mnemonic(), arguments, written)?;
+        self.initialized(line, column)?;
+
+        self.image().push(instruction.into());
+        let mut kinds = kinds.iter().peekable();
+        for argument in arguments {
+            let kind = kinds.next().expect("arity matches the operand kinds");
+            let displaced = kinds.next_if_eq(&&OperandKind::Offset).is_some();
+            if kinds.next_if_eq(&&OperandKind::Wide).is_some() {
+                self.wide(line, argument)?;
+                continue;
+            }
+            if *kind == OperandKind::StackOffset {
+                let Operand::Memory(base, offset) = &argument.operand
+                else {
+                    return Err(error(argument.column, AssemblyErrorKind::ExpectedStackSlot));
+                };
+                if **base != Operand::StackPointer {
+                    return Err(error(argument.column, AssemblyErrorKind::ExpectedStackSlot));
+                }
+                self.emit(line, &Argument { operand: Operand::Expression(offset.clone()), column: argument.column })?;
+                continue;
+            }
+            match (displaced, &argument.operand) {
+                | (true, Operand::Memory(base, offset)) => {
+                    self.register(line, *kind, base, argument.column)?;
+                    self.emit(
+                        line,
+                        &Argument { operand: Operand::Expression(offset.clone()), column: argument.column },
+                    )?;
+                }
+                | (true, _) => return Err(error(argument.column, AssemblyErrorKind::ExpectedMemory)),
+                | (false, operand) if matches!(kind, OperandKind::Register | OperandKind::Pair) => {
+                    self.register(line, *kind, operand, argument.column)?;
+                }
+                | (false, Operand::Text(_)) => {
+                    return Err(error(argument.column, AssemblyErrorKind::ExpectedValue));
+                }
+                | (false, _) => self.emit(line, argument)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// emits a sixteen bit value high byte first, relocating each half on its own
+    fn wide(&mut self, line: usize, argument: &Argument) -> Result<(), AssemblyError> {
+        let Operand::Expression(expression) = &argument.operand
+        else {
+            return Err(AssemblyError { line, column: argument.column, kind: AssemblyErrorKind::ExpectedValue });
+        };
+        for half in [UnaryOperator::High, UnaryOperator::Low] {
+            let operand = Operand::Expression(Expression::Unary(half, Box::new(expression.clone())));
+            self.emit(line, &Argument { operand, column: argument.column })?;
+        }
+        Ok(())
+    }
+
+    /// encodes a register or the high register of a pair, checking both halves exist
+    fn register(
+        &mut self,
+        line: usize,
+        kind: OperandKind,
+        operand: &Operand,
+        column: usize,
+    ) -> Result<(), AssemblyError> {
+        let error = |kind| AssemblyError { line, column, kind };
+        let (index, last) = match (kind, operand) {
+            | (OperandKind::Register, Operand::Register(index)) => (*index, *index),
+            | (OperandKind::Pair, Operand::Pair(index)) => (*index, index + 1),
+            | (OperandKind::Pair, _) => return Err(error(AssemblyErrorKind::ExpectedPair)),
+            | _ => return Err(error(AssemblyErrorKind::ExpectedRegister)),
+        };
+        if last >= self.registers {
+            return Err(error(AssemblyErrorKind::RegisterOutOfRange { index: last, count: self.registers }));
+        }
+        self.image().push(index as Data);
+        Ok(())
+    }
+
+    fn directive(
+        &mut self,
+        line: usize,
+        name: &str,
+        column: usize,
+        arguments: &[Argument],
+    ) -> Result<(), AssemblyError> {
+        let error = |column, kind| AssemblyError { line, column, kind };
+        // where a sizing directive leaves the section, which must stay inside the address space
+        let within = |argument: &Argument, value: i64, end: Option<usize>| {
+            end.filter(|end| *end <= ADDRESS_SPACE)
+                .ok_or_else(|| error(argument.column, AssemblyErrorKind::ValueOutOfRange(value)))
+        };
+        match name.to_ascii_lowercase().as_str() {
+            // pads the current section to the origin, an offset into it that keeps the unit relocatable
+            | ".org" => {
+                arity(line, column, ".org", arguments, 1)?;
+                let origin = self.constant(line, &arguments[0])?;
+                let location = self.location() as i64;
+                if origin < location {
+                    return Err(error(arguments[0].column, AssemblyErrorKind::OriginBackwards { origin, location }));
+                }
+                let end = within(&arguments[0], origin, usize::try_from(origin).ok())?;
+                self.image().resize(end, Default::default());
+            }
+            | ".byte" => {
+                if arguments.is_empty() {
+                    return Err(error(column + name.len(), AssemblyErrorKind::ExpectedOperand));
+                }
+                for argument in arguments {
+                    self.emit(line, argument)?;
+                }
+            }
+            | ".string" => {
+                arity(line, column, ".string", arguments, 1)?;
+                let Operand::Text(text) = &arguments[0].operand
+                else {
+                    return Err(error(arguments[0].column, AssemblyErrorKind::ExpectedText));
+                };
+                self.initialized(line, column)?;
+                self.image().extend(text.bytes());
+                self.image().push(Default::default());
+            }
+            | ".fill" => {
+                arity(line, column, ".fill", arguments, 2)?;
+                let count = self.constant(line, &arguments[0])?;
+                let end = usize::try_from(count).ok().and_then(|count| self.location().checked_add(count));
+                let count = within(&arguments[0], count, end)? - self.location();
+                for _ in 0..count {
+                    self.emit(line, &arguments[1])?;
+                }
+            }
+            | ".align" => {
+                arity(line, column, ".align", arguments, 1)?;
+                let alignment = self.constant(line, &arguments[0])?;
+                let padded = usize::try_from(alignment)
+                    .ok()
+                    .and_then(|alignment| self.location().checked_next_multiple_of(alignment));
+                let padded = within(&arguments[0], alignment, padded)?;
+                self.image().resize(padded, Default::default());
+            }
+            | ".space" => {
+                arity(line, column, ".space", arguments, 1)?;
+                let size = self.constant(line, &arguments[0])?;
+                let end = usize::try_from(size).ok().and_then(|size| self.location().checked_add(size));
+                let end = within(&arguments[0], size, end)?;
+                self.image().resize(end, Default::default());
+            }
+            | section @ (".code" | ".data" | ".bss") => {
+                arity(line, column, ".section", arguments, 0)?;
+                self.section = Section::from_name(&section[1..]).expect("matched a section name");
+            }
+            | directive @ (".global" | ".extern") => {
+                if arguments.is_empty() {
+                    return Err(error(column + name.len(), AssemblyErrorKind::ExpectedSymbol));
+                }
+                for argument in arguments {
+                    let Operand::Expression(Expression::Symbol(symbol)) = &argument.operand
+                    else {
+                        return Err(error(argument.column, AssemblyErrorKind::ExpectedSymbol));
+                    };
+                    match directive {
+                        | ".global" => self.globals.push((symbol.clone(), line, argument.column)),
+                        | _ => self.define(symbol, 0, SymbolKind::External, line, argument.column)?,
+                    }
+                }
+            }
+            | ".equ" => {
+                arity(line, column, ".equ", arguments, 2)?;
+                let Operand::Expression(Expression::Symbol(symbol)) = &arguments[0].operand
+                else {
+                    return Err(error(arguments[0].column, AssemblyErrorKind::ExpectedSymbol));
+                };
+                let value = self.constant(line, &arguments[1])?;
+                self.define(symbol, value, SymbolKind::Constant, line, arguments[0].column)?;
+            }
+            | _ => return Err(error(column, AssemblyErrorKind::UnknownDirective(name.into()))),
+        }
+
+        Ok(())
+    }
+
+    fn emit(&mut self, line: usize, argument: &Argument) -> Result<(), AssemblyError> {
+        match &argument.operand {
+            | Operand::Expression(expression) => {
+                self.initialized(line, argument.column)?;
+                let (section, offset) = (self.section, self.location());
+                let column = argument.column;
+                self.fixups.push(Fixup { section, offset, expression: expression.clone(), line, column });
+                self.image().push(Default::default());
+            }
+            | Operand::Text(text) => {
+                self.initialized(line, argument.column)?;
+                self.image().extend(text.bytes());
+            }
+            | Operand::Register(_) | Operand::Pair(_) | Operand::StackPointer | Operand::Memory(..) => {
+                return Err(AssemblyError { line, column: argument.column, kind: AssemblyErrorKind::ExpectedValue });
+            }
+        }
+        Ok(())
+    }
+
+    fn constant(&self, line: usize, argument: &Argument) -> Result<i64, AssemblyError> {
+        let error = |kind| AssemblyError { line, column: argument.column, kind };
+        let Operand::Expression(expression) = &argument.operand
+        else {
+            return Err(error(AssemblyErrorKind::ExpectedValue));
+        };
+        match expression.evaluate(&self.symbols) {
+            | Ok(Evaluated { value, relocation: None }) => Ok(value),
+            | Ok(_) => Err(error(AssemblyErrorKind::ExpectedConstant(expression.to_string()))),
+            | Err(AssemblyErrorKind::UndefinedSymbol(name)) => Err(error(AssemblyErrorKind::ExpectedConstant(name))),
+            | Err(kind) => Err(error(kind)),
+        }
+    }
+}
+
+/// accepts both the unsigned and the two's complement spelling of a data word
+pub fn data_word(value: i64) -> Option<Data> {
+    Data::try_from(value).ok().or_else(|| i8::try_from(value).ok().map(|value| value as Data))
+}
+
+/// finds the first word on a line that is not a label definition
+fn keyword(lexemes: &[Lexeme]) -> Option<(usize, &str)> {
+    let mut head = 0;
+    while let Some(Lexeme { token: Token::Identifier(name), .. }) = lexemes.get(head) {
+        match lexemes.get(head + 1) {
+            | Some(Lexeme { token: Token::Colon, .. }) => head += 2,
+            | _ => return Some((head, name)),
+        }
+    }
+    None
+}
+
+fn arity(
+    line: usize,
+    column: usize,
+    mnemonic: &'static str,
+    arguments: &[Argument],
+    expected: usize,
+) -> Result<(), AssemblyError> {
+    if arguments.len() != expected {
+        let kind = AssemblyErrorKind::OperandCount { mnemonic, expected, found: arguments.len() };
+        return Err(AssemblyError { line, column, kind });
+    }
+    Ok(())
+}
@@ -0,0 +1,312 @@
+use crate::assembler::AssemblyError;
+use crate::assembler::AssemblyErrorKind;
+use crate::assembler::assembly::Definition;
+use crate::assembler::assembly::SymbolKind;
+use crate::assembler::lexer::Lexeme;
+use crate::assembler::lexer::Token;
+use crate::assembler::parser::unexpected;
+use crate::object::RelocationKind;
+use crate::object::Target;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOperator {
+    Negate,
+    Not,
+    Low,
+    High,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOperator {
+    Or,
+    Xor,
+    And,
+    ShiftLeft,
+    ShiftRight,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+/// binary operators grouped from loosest to tightest binding
+const PRECEDENCE: [&[(&str, BinaryOperator)]; 6] = [
+    &[("|", BinaryOperator::Or)],
+    &[("^", BinaryOperator::Xor)],
+    &[("&", BinaryOperator::And)],
+    &[("<<", BinaryOperator::ShiftLeft), (">>", BinaryOperator::ShiftRight)],
+    &[("+", BinaryOperator::Add), ("-", BinaryOperator::Sub)],
+    &[("*", BinaryOperator::Mul), ("/", BinaryOperator::Div), ("%", BinaryOperator::Rem)],
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expression {
+    Value(i64),
+    Symbol(String),
+    Unary(UnaryOperator, Box<Expression>),
+    Binary(BinaryOperator, Box<Expression>, Box<Expression>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Evaluated {
+    pub value: i64,
+    pub relocation: Option<(RelocationKind, Target)>,
+}
+
+impl std::fmt::Display for Expression {
+    fn fmt(&self, frmtr: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let nested = |frmtr: &mut std::fmt::Formatter<'_>, expression: &Expression| match expression {
+            | Expression::Binary(..) => write!(frmtr, "({expression})"),
+            | _ => write!(frmtr, "{expression}"),
+        };
+
+        match self {
+            | Expression::Value(value) => write!(frmtr, "{value}"),
+            | Expression::Symbol(name) => write!(frmtr, "{name}"),
+            | Expression::Unary(operator, operand) => {
+                let symbol = match operator {
+                    | UnaryOperator::Negate => "-",
+                    | UnaryOperator::Not => "~",
+                    | UnaryOperator::Low => "<",
+                    | UnaryOperator::High => ">",
+                };
+                write!(frmtr, "{symbol}")?;
+                nested(frmtr, operand)
+            }
+            | Expression::Binary(operator, lhs, rhs) => {
+                let (symbol, _) = PRECEDENCE
+                    .iter()
+                    .flat_map(|level| level.iter())
+                    .find(|(_, candidate)| candidate == operator)
+                    .expect("every binary operator has a precedence");
+                nested(frmtr, lhs)?;
+                write!(frmtr, "{symbol}")?;
+                nested(frmtr, rhs)
+            }
+        }
+    }
+}
+
+impl Expression {
+    pub fn evaluate(&self, symbols: &HashMap<String, Definition>) -> Result<Evaluated, AssemblyErrorKind> {
+        let overflow = || AssemblyErrorKind::ExpressionOverflow { expression: self.to_string() };
+        let relocation = || AssemblyErrorKind::InvalidRelocation { expression: self.to_string() };
+        let absolute = |value| Ok(Evaluated { value, relocation: None });
+
+        match self {
+            | Expression::Value(value) => absolute(*value),
+            | Expression::Symbol(name) => match symbols.get(name) {
+                | Some(Definition { value, kind: SymbolKind::Label(section), .. }) => {
+                    Ok(Evaluated { value: *value, relocation: Some((RelocationKind::Byte, Target::Section(*section))) })
+                }
+                | Some(Definition { value, kind: SymbolKind::Constant, .. }) => absolute(*value),
+                | Some(Definition { kind: SymbolKind::External, .. }) => {
+                    Ok(Evaluated { value: 0, relocation: Some((RelocationKind::Byte, Target::External(name.clone()))) })
+                }
+                | None => Err(AssemblyErrorKind::UndefinedSymbol(name.clone())),
+            },
+            | Expression::Unary(operator, operand) => {
+                let Evaluated { value, relocation: relocatable } = operand.evaluate(symbols)?;
+                match (operator, relocatable) {
+                    | (UnaryOperator::Low, None) => absolute(value & 0xFF),
+                    | (UnaryOperator::High, None) => absolute((value >> 8) & 0xFF),
+                    | (UnaryOperator::Low, Some((RelocationKind::Byte, target))) => {
+                        Ok(Evaluated { value, relocation: Some((RelocationKind::Low, target)) })
+                    }
+                    | (UnaryOperator::High, Some((RelocationKind::Byte, target))) => {
+                        Ok(Evaluated { value, relocation: Some((RelocationKind::High, target)) })
+                    }
+                    | (UnaryOperator::Negate, None) => value.checked_neg().map_or_else(|| Err(overflow()), absolute),
+                    | (UnaryOperator::Not, None) => absolute(!value),
+                    | (_, Some(_)) => Err(relocation()),
+                }
+            }
+            | Expression::Binary(operator, lhs, rhs) => {
+                let lhs = lhs.evaluate(symbols)?;
+                let rhs = rhs.evaluate(symbols)?;
+                let relocatable = match (operator, lhs.relocation, rhs.relocation) {
+                    | (_, None, None) => None,
+                    | (BinaryOperator::Add, Some((RelocationKind::Byte, target)), None)
+                    | (BinaryOperator::Add, None, Some((RelocationKind::Byte, target)))
+                    | (BinaryOperator::Sub, Some((RelocationKind::Byte, target)), None) => {
+                        Some((RelocationKind::Byte, target))
+                    }
+                    | (
+                        BinaryOperator::Sub,
+                        Some((RelocationKind::Byte, Target::Section(lhs))),
+                        Some((RelocationKind::Byte, Target::Section(rhs))),
+                    ) if lhs == rhs => None,
+                    | _ => return Err(relocation()),
+                };
+
+                let (x, y) = (lhs.value, rhs.value);
+                let value = match operator {
+                    | BinaryOperator::Or => Some(x | y),
+                    | BinaryOperator::Xor => Some(x ^ y),
+                    | BinaryOperator::And => Some(x & y),
+                    | BinaryOperator::ShiftLeft => u32::try_from(y).ok().and_then(|y| x.checked_shl(y)),
+                    | BinaryOperator::ShiftRight => u32::try_from(y).ok().and_then(|y| x.checked_shr(y)),
+                    | BinaryOperator::Add => x.checked_add(y),
+                    | BinaryOperator::Sub => x.checked_sub(y),
+                    | BinaryOperator::Mul => x.checked_mul(y),
+                    | BinaryOperator::Div | BinaryOperator::Rem if y == 0 => {
+                        return Err(AssemblyErrorKind::DivisionByZero { expression: self.to_string() });
+                    }
+                    | BinaryOperator::Div => x.checked_div(y),
+                    | BinaryOperator::Rem => x.checked_rem(y),
+                };
+                value.map(|value| Evaluated { value, relocation: relocatable }).ok_or_else(overflow)
+            }
+        }
+    }
+}
+
+pub fn parse(line: usize, lexemes: &[Lexeme]) -> Result<Expression, AssemblyError> {
+    let mut parser = ExpressionParser { line, lexemes, head: 0 };
+    let expression = parser.binary(0)?;
+    match lexemes.get(parser.head) {
+        | Some(lexeme) => Err(unexpected(line, lexeme)),
+        | None => Ok(expression),
+    }
+}
+
+struct ExpressionParser<'d> {
+    line: usize,
+    lexemes: &'d [Lexeme],
+    head: usize,
+}
+
+impl ExpressionParser<'_> {
+    fn operator(&self) -> Option<&'static str> {
+        match self.lexemes.get(self.head) {
+            | Some(Lexeme { token: Token::Operator(operator), .. }) => Some(operator),
+            | _ => None,
+        }
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expression, AssemblyError> {
+        let Some(operators) = PRECEDENCE.get(level)
+        else {
+            return self.unary();
+        };
+
+        let mut lhs = self.binary(level + 1)?;
+        while let Some((_, operator)) =
+            self.operator().and_then(|symbol| operators.iter().find(|(candidate, _)| *candidate == symbol))
+        {
+            self.head += 1;
+            let rhs = self.binary(level + 1)?;
+            lhs = Expression::Binary(*operator, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expression, AssemblyError> {
+        let Some(lexeme) = self.lexemes.get(self.head)
+        else {
+            let column = self.lexemes.last().map_or(1, |lexeme| lexeme.column + 1);
+            return Err(AssemblyError { line: self.line, column, kind: AssemblyErrorKind::ExpectedOperand });
+        };
+        self.head += 1;
+
+        let operator = match &lexeme.token {
+            | Token::Number(value) => return Ok(Expression::Value(*value)),
+            | Token::Identifier(name) => return Ok(Expression::Symbol(name.clone())),
+            | Token::Operator("(") => {
+                let inner = self.binary(0)?;
+                return match self.operator() {
+                    | Some(")") => {
+                        self.head += 1;
+                        Ok(inner)
+                    }
+                    | _ => {
+                        let column = self.lexemes.get(self.head).map_or(lexeme.column, |lexeme| lexeme.column);
+                        Err(AssemblyError { line: self.line, column, kind: AssemblyErrorKind::UnclosedParenthesis })
+                    }
+                };
+            }
+            | Token::Operator("-") => UnaryOperator::Negate,
+            | Token::Operator("~") => UnaryOperator::Not,
+            | Token::Operator("<") => UnaryOperator::Low,
+            | Token::Operator(">") => UnaryOperator::High,
+            | _ => return Err(unexpected(self.line, lexeme)),
+        };
+        Ok(Expression::Unary(operator, Box::new(self.unary()?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assembler::lexer::tokenize;
+    use crate::object::Section;
+
+    use super::*;
+
+    fn evaluate(text: &str, symbols: &HashMap<String, Definition>) -> Result<Evaluated, AssemblyErrorKind> {
+        parse(1, &tokenize(1, text).unwrap()).unwrap().evaluate(symbols)
+    }
+
+    fn symbols() -> HashMap<String, Definition> {
+        HashMap::from([
+            ("SIZE".into(), Definition { value: 6, kind: SymbolKind::Constant, line: 1 }),
+            ("start".into(), Definition { value: 0x10, kind: SymbolKind::Label(Section::Code), line: 2 }),
+            ("end".into(), Definition { value: 0x134, kind: SymbolKind::Label(Section::Code), line: 3 }),
+            ("buffer".into(), Definition { value: 0, kind: SymbolKind::Label(Section::Bss), line: 4 }),
+            ("print".into(), Definition { value: 0, kind: SymbolKind::External, line: 5 }),
+        ])
+    }
+
+    fn code(kind: RelocationKind) -> Option<(RelocationKind, Target)> {
+        Some((kind, Target::Section(Section::Code)))
+    }
+
+    #[test]
+    fn precedence() {
+        let symbols = symbols();
+        assert!(evaluate("1 + 2 * 3", &symbols).unwrap().value == 7);
+        assert!(evaluate("(SIZE*2)|1", &symbols).unwrap().value == 13);
+        assert!(evaluate("1 << 2 + 1", &symbols).unwrap().value == 8);
+        assert!(evaluate("-SIZE + ~0", &symbols).unwrap().value == -7);
+    }
+
+    #[test]
+    fn relocatable_results() {
+        let symbols = symbols();
+        assert!(evaluate("end-start", &symbols).unwrap() == Evaluated { value: 0x124, relocation: None });
+        assert!(
+            evaluate("start+4", &symbols).unwrap() == Evaluated { value: 0x14, relocation: code(RelocationKind::Byte) }
+        );
+        assert!(
+            evaluate(">end", &symbols).unwrap() == Evaluated { value: 0x134, relocation: code(RelocationKind::High) }
+        );
+        assert!(evaluate(">(end-start)", &symbols).unwrap() == Evaluated { value: 1, relocation: None });
+        assert!(
+            evaluate("start*2", &symbols) == Err(AssemblyErrorKind::InvalidRelocation { expression: "start*2".into() })
+        );
+    }
+
+    #[test]
+    fn cross_section_results() {
+        let symbols = symbols();
+        let external = Some((RelocationKind::Low, Target::External("print".into())));
+        assert!(evaluate("<(print+2)", &symbols).unwrap() == Evaluated { value: 2, relocation: external });
+        let invalid = |expression: &str| Err(AssemblyErrorKind::InvalidRelocation { expression: expression.into() });
+        assert!(evaluate("buffer-start", &symbols) == invalid("buffer-start"));
+        assert!(evaluate("print-print", &symbols) == invalid("print-print"));
+    }
+
+    #[test]
+    fn failures() {
+        let symbols = symbols();
+        assert!(
+            evaluate("SIZE / (1 - 1)", &symbols)
+                == Err(AssemblyErrorKind::DivisionByZero { expression: "SIZE/(1-1)".into() })
+        );
+        assert!(
+            evaluate("1 << 64", &symbols) == Err(AssemblyErrorKind::ExpressionOverflow { expression: "1<<64".into() })
+        );
+        assert!(parse(1, &tokenize(1, "(1 + 2").unwrap()).unwrap_err().kind == AssemblyErrorKind::UnclosedParenthesis);
+    }
+}
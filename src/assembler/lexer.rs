@@ -0,0 +1,200 @@
+use crate::assembler::AssemblyError;
+use crate::assembler::AssemblyErrorKind;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    Identifier(String),
+    Number(i64),
+    Text(String),
+    Operator(&'static str),
+    Comma,
+    Colon,
+}
+
+const OPERATORS: [&str; 17] = ["<<", ">>", "+", "-", "*", "/", "%", "&", "|", "^", "~", "<", ">", "(", ")", "[", "]"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lexeme {
+    pub token: Token,
+    pub column: usize,
+}
+
+pub fn tokenize(line: usize, text: &str) -> Result<Vec<Lexeme>, AssemblyError> {
+    let chars = text.chars().collect::<Vec<char>>();
+    let mut tokens = Vec::new();
+    let mut head = 0;
+
+    while head < chars.len() {
+        let column = head + 1;
+        let current = chars[head];
+        let error = |kind| AssemblyError { line, column, kind };
+
+        match current {
+            | ';' => break,
+            | ',' => {
+                tokens.push(Lexeme { token: Token::Comma, column });
+                head += 1;
+            }
+            | ':' => {
+                tokens.push(Lexeme { token: Token::Colon, column });
+                head += 1;
+            }
+            | '"' => {
+                let (text, length) = string_literal(&chars[head..])
+                    .ok_or_else(|| error(AssemblyErrorKind::InvalidLiteral(chars[head..].iter().collect())))?;
+                tokens.push(Lexeme { token: Token::Text(text), column });
+                head += length;
+            }
+            | '\'' => {
+                let (value, length) = char_literal(&chars[head..]).ok_or_else(|| {
+                    let literal = chars[head..].iter().take_while(|chr| !chr.is_whitespace()).collect();
+                    error(AssemblyErrorKind::InvalidLiteral(literal))
+                })?;
+                tokens.push(Lexeme { token: Token::Number(value), column });
+                head += length;
+            }
+            | chr if chr.is_ascii_digit() => {
+                let literal = chars[head..].iter().take_while(|chr| is_word(**chr)).collect::<String>();
+                let value = number_literal(&literal)
+                    .ok_or_else(|| error(AssemblyErrorKind::InvalidLiteral(literal.clone())))?;
+                tokens.push(Lexeme { token: Token::Number(value), column });
+                head += literal.chars().count();
+            }
+            | chr if is_word_start(chr) => {
+                let word = chars[head..].iter().take_while(|chr| is_word(**chr)).collect::<String>();
+                head += word.chars().count();
+                tokens.push(Lexeme { token: Token::Identifier(word), column });
+            }
+            | chr if chr.is_whitespace() => head += 1,
+            | _ if let Some(operator) = operator(&chars[head..]) => {
+                tokens.push(Lexeme { token: Token::Operator(operator), column });
+                head += operator.len();
+            }
+            | chr => return Err(error(AssemblyErrorKind::UnexpectedCharacter(chr))),
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn operator(chars: &[char]) -> Option<&'static str> {
+    OPERATORS.into_iter().find(|operator| chars.iter().take(operator.len()).copied().eq(operator.chars()))
+}
+
+fn is_word_start(chr: char) -> bool {
+    chr.is_ascii_alphabetic() || chr == '_' || chr == '.' || chr == '@'
+}
+
+fn is_word(chr: char) -> bool {
+    chr.is_ascii_alphanumeric() || chr == '_' || chr == '.' || chr == '@'
+}
+
+fn number_literal(literal: &str) -> Option<i64> {
+    let digits = literal.replace('_', "");
+    let lower = digits.to_ascii_lowercase();
+    let (radix, body) = match lower.get(..2) {
+        | Some("0x") => (16, &lower[2..]),
+        | Some("0b") => (2, &lower[2..]),
+        | Some("0o") => (8, &lower[2..]),
+        | _ => (10, &lower[..]),
+    };
+    if body.is_empty() {
+        return None;
+    }
+    i64::from_str_radix(body, radix).ok()
+}
+
+fn char_literal(chars: &[char]) -> Option<(i64, usize)> {
+    let (value, length) = match chars.get(1..)? {
+        | ['\\', escaped, ..] => (escape(*escaped)?, 2),
+        | [chr, ..] if *chr != '\'' && chr.is_ascii() => (*chr, 1),
+        | _ => return None,
+    };
+    match chars.get(length + 1)? {
+        | '\'' => Some((value as i64, length + 2)),
+        | _ => None,
+    }
+}
+
+fn string_literal(chars: &[char]) -> Option<(String, usize)> {
+    let mut text = String::new();
+    let mut head = 1;
+    loop {
+        match chars.get(head)? {
+            | '"' => return Some((text, head + 1)),
+            | '\\' => {
+                text.push(escape(*chars.get(head + 1)?)?);
+                head += 2;
+            }
+            | chr if chr.is_ascii() => {
+                text.push(*chr);
+                head += 1;
+            }
+            | _ => return None,
+        }
+    }
+}
+
+fn escape(escaped: char) -> Option<char> {
+    match escaped {
+        | 'n' => Some('\n'),
+        | 't' => Some('\t'),
+        | 'r' => Some('\r'),
+        | '0' => Some('\0'),
+        | '\\' | '\'' | '"' => Some(escaped),
+        | _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(text: &str) -> Vec<Token> {
+        tokenize(1, text).unwrap().into_iter().map(|lexeme| lexeme.token).collect()
+    }
+
+    #[test]
+    fn literals() {
+        assert!(tokens("10 0x1F 0b101 'a' '\\n' 1_000") == [10, 31, 5, 97, 10, 1000].map(Token::Number));
+    }
+
+    #[test]
+    fn operators() {
+        let expected = vec![
+            Token::Operator("<"),
+            Token::Identifier("buffer".into()),
+            Token::Operator("<<"),
+            Token::Operator("("),
+            Token::Number(2),
+            Token::Operator("-"),
+            Token::Number(1),
+            Token::Operator(")"),
+        ];
+        assert!(tokens("<buffer<<(2-1)") == expected);
+    }
+
+    #[test]
+    fn strings() {
+        assert!(tokens(r#".string "hi; there\n""#)[1] == Token::Text("hi; there\n".into()));
+        assert!(tokenize(1, r#".string "open"#).is_err());
+    }
+
+    #[test]
+    fn instruction_line() {
+        let expected = vec![
+            Token::Identifier("ADD".into()),
+            Token::Identifier("r0".into()),
+            Token::Comma,
+            Token::Identifier("r1".into()),
+        ];
+        assert!(tokens("ADD r0, r1 ; trailing comment") == expected);
+    }
+
+    #[test]
+    fn bad_literal() {
+        let error = tokenize(4, "  LOADIMM r0, 0xZZ").unwrap_err();
+        assert!(error.line == 4 && error.column == 15);
+        assert!(error.kind == AssemblyErrorKind::InvalidLiteral("0xZZ".into()));
+    }
+}
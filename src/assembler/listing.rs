@@ -0,0 +1,63 @@
+use crate::cpu::Data;
+use crate::object::Section;
+
+/// bytes shown beside each source line before the rest is elided
+const LISTING_WIDTH: usize = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListingLine {
+    pub line: usize,
+    pub section: Section,
+    pub address: usize,
+    pub bytes: Vec<Data>,
+    pub source: String,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Listing {
+    pub lines: Vec<ListingLine>,
+}
+
+impl std::fmt::Display for Listing {
+    fn fmt(&self, frmtr: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(frmtr, "{:>5}  {:<6}  {:<14}  source", "line", "addr", "bytes")?;
+        for line in &self.lines {
+            let mut bytes =
+                line.bytes.iter().take(LISTING_WIDTH).map(|byte| format!("{byte:02x}")).collect::<Vec<String>>();
+            if line.bytes.len() > LISTING_WIDTH {
+                bytes.push("..".into());
+            }
+            let address = match line.bytes.is_empty() {
+                | true => String::new(),
+                | false => format!("{:#06x}", line.address),
+            };
+            writeln!(frmtr, "{:>5}  {address:<6}  {:<14}  {}", line.line, bytes.join(" "), line.source.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elided_bytes() {
+        let listing = Listing {
+            lines: vec![
+                ListingLine { line: 1, section: Section::Code, address: 0, bytes: vec![], source: "; header".into() },
+                ListingLine {
+                    line: 2,
+                    section: Section::Data,
+                    address: 0x10,
+                    bytes: vec![1, 2, 3, 4, 5],
+                    source: "  .byte 1, 2, 3, 4, 5".into(),
+                },
+            ],
+        };
+        let text = listing.to_string();
+        let rows = text.lines().collect::<Vec<&str>>();
+        assert!(rows[1] == "    1                          ; header");
+        assert!(rows[2] == "    2  0x0010  01 02 03 04 ..    .byte 1, 2, 3, 4, 5");
+    }
+}
@@ -0,0 +1,93 @@
+use crate::assembler::AssemblyError;
+use crate::assembler::AssemblyErrorKind;
+use crate::assembler::lexer::Lexeme;
+use crate::assembler::lexer::Token;
+use crate::assembler::parser::unexpected;
+
+pub const EXPANSION_LIMIT: usize = 16;
+
+/// labels with this prefix are renamed on every expansion so each copy of the body gets its own
+pub const LOCAL_PREFIX: char = '@';
+
+#[derive(Debug, Clone)]
+pub struct Macro {
+    pub name: String,
+    pub parameters: Vec<String>,
+    pub body: Vec<(usize, Vec<Lexeme>)>,
+    pub line: usize,
+}
+
+impl Macro {
+    /// parses the `NAME param, param` tail of a `.macro` line
+    pub fn define(line: usize, column: usize, lexemes: &[Lexeme]) -> Result<Self, AssemblyError> {
+        let Some((Lexeme { token: Token::Identifier(name), .. }, rest)) = lexemes.split_first()
+        else {
+            let kind = AssemblyErrorKind::ExpectedSymbol;
+            return Err(AssemblyError { line, column: lexemes.first().map_or(column, |lexeme| lexeme.column), kind });
+        };
+
+        let mut parameters = Vec::new();
+        for group in split_arguments(rest) {
+            match group {
+                | [Lexeme { token: Token::Identifier(parameter), .. }] => parameters.push(parameter.clone()),
+                | [lexeme] | [_, lexeme, ..] => return Err(unexpected(line, lexeme)),
+                | [] => {
+                    let column = rest.first().map_or(column, |lexeme| lexeme.column);
+                    return Err(AssemblyError { line, column, kind: AssemblyErrorKind::ExpectedSymbol });
+                }
+            }
+        }
+
+        Ok(Self { name: name.clone(), parameters, body: Vec::new(), line })
+    }
+
+    pub fn expand(&self, arguments: &[&[Lexeme]], expansion: usize) -> Vec<(usize, Vec<Lexeme>)> {
+        let substitute = |lexeme: &Lexeme| match &lexeme.token {
+            | Token::Identifier(name) => match self.parameters.iter().position(|parameter| parameter == name) {
+                | Some(position) => arguments[position].to_vec(),
+                | None if name.starts_with(LOCAL_PREFIX) => {
+                    vec![Lexeme { token: Token::Identifier(format!("{name}.{expansion}")), column: lexeme.column }]
+                }
+                | None => vec![lexeme.clone()],
+            },
+            | _ => vec![lexeme.clone()],
+        };
+
+        self.body.iter().map(|(line, lexemes)| (*line, lexemes.iter().flat_map(substitute).collect())).collect()
+    }
+}
+
+/// splits raw operand tokens on commas, keeping each argument as written so it can be pasted into a body
+pub fn split_arguments(lexemes: &[Lexeme]) -> Vec<&[Lexeme]> {
+    if lexemes.is_empty() {
+        return Vec::new();
+    }
+    lexemes.split(|lexeme| lexeme.token == Token::Comma).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assembler::lexer::tokenize;
+
+    use super::*;
+
+    #[test]
+    fn substitution() {
+        let mut definition = Macro::define(1, 1, &tokenize(1, "SUM dst, x").unwrap()).unwrap();
+        definition.body.push((2, tokenize(2, "@top: ADD dst, x, x").unwrap()));
+        let argument = tokenize(3, "r1").unwrap();
+        let value = tokenize(3, "r2").unwrap();
+
+        let expanded = definition.expand(&[&argument, &value], 4);
+        let tokens = expanded[0].1.iter().map(|lexeme| lexeme.token.clone()).collect::<Vec<Token>>();
+        assert!(tokens[0] == Token::Identifier("@top.4".into()));
+        assert!(tokens[3] == Token::Identifier("r1".into()));
+        assert!(tokens[5] == Token::Identifier("r2".into()));
+    }
+
+    #[test]
+    fn bad_parameter() {
+        let error = Macro::define(1, 1, &tokenize(1, "SUM dst, 4").unwrap()).unwrap_err();
+        assert!(error.kind == AssemblyErrorKind::UnexpectedToken("4".into()));
+    }
+}
@@ -0,0 +1,284 @@
+use crate::assembler::AssemblyError;
+use crate::assembler::AssemblyErrorKind;
+use crate::assembler::expression;
+use crate::assembler::expression::Expression;
+use crate::assembler::lexer::Lexeme;
+use crate::assembler::lexer::Token;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operand {
+    Register(usize),
+    /// `r2:r3`, kept as the index of the high register
+    Pair(usize),
+    /// `sp`, only valid as the base of a memory operand
+    StackPointer,
+    /// `[r2+4]`, `[r2:r3-1]` or `[sp+1]`, a base plus an offset
+    Memory(Box<Operand>, Expression),
+    Expression(Expression),
+    Text(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Argument {
+    pub operand: Operand,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Statement {
+    Label { name: String, column: usize },
+    Instruction { mnemonic: String, column: usize, arguments: Vec<Argument> },
+    Directive { name: String, column: usize, arguments: Vec<Argument> },
+}
+
+pub fn parse_line(line: usize, lexemes: &[Lexeme]) -> Result<Vec<Statement>, AssemblyError> {
+    let mut statements = Vec::new();
+    let mut head = 0;
+
+    while let Some(first) = lexemes.get(head) {
+        let Token::Identifier(name) = &first.token
+        else {
+            return Err(unexpected(line, first));
+        };
+        head += 1;
+
+        if let Some(Lexeme { token: Token::Colon, .. }) = lexemes.get(head) {
+            head += 1;
+            if register_index(name).is_some() {
+                return Err(unexpected(line, first));
+            }
+            statements.push(Statement::Label { name: name.clone(), column: first.column });
+            continue;
+        }
+
+        let arguments = parse_arguments(line, first.column + name.len(), &lexemes[head..])?;
+        head = lexemes.len();
+        match name.starts_with('.') {
+            | true => statements.push(Statement::Directive { name: name.clone(), column: first.column, arguments }),
+            | false => {
+                statements.push(Statement::Instruction { mnemonic: name.clone(), column: first.column, arguments })
+            }
+        }
+    }
+
+    Ok(statements)
+}
+
+fn parse_arguments(line: usize, mut column: usize, lexemes: &[Lexeme]) -> Result<Vec<Argument>, AssemblyError> {
+    let mut arguments = Vec::new();
+    if lexemes.is_empty() {
+        return Ok(arguments);
+    }
+
+    let mut rest = lexemes;
+    loop {
+        let end = rest.iter().position(|lexeme| lexeme.token == Token::Comma).unwrap_or(rest.len());
+        let (group, tail) = rest.split_at(end);
+        if group.is_empty() {
+            let column = tail.first().map_or(column, |comma| comma.column);
+            return Err(AssemblyError { line, column, kind: AssemblyErrorKind::ExpectedOperand });
+        }
+        arguments.push(parse_operand(line, group)?);
+
+        let Some((comma, remaining)) = tail.split_first()
+        else {
+            break;
+        };
+        column = comma.column + 1;
+        rest = remaining;
+    }
+    Ok(arguments)
+}
+
+fn parse_operand(line: usize, lexemes: &[Lexeme]) -> Result<Argument, AssemblyError> {
+    let column = lexemes[0].column;
+    let operand = match lexemes {
+        | [Lexeme { token: Token::Text(text), .. }] => Operand::Text(text.clone()),
+        | [Lexeme { token: Token::Operator("["), .. }, ..] => parse_memory(line, lexemes)?,
+        | _ => match parse_registers(line, lexemes)? {
+            | Some((operand, [])) => operand,
+            | _ => Operand::Expression(expression::parse(line, lexemes)?),
+        },
+    };
+    Ok(Argument { operand, column })
+}
+
+/// reads the `[base+offset]` form, the offset is optional and may be negative
+fn parse_memory(line: usize, lexemes: &[Lexeme]) -> Result<Operand, AssemblyError> {
+    let Some((Lexeme { token: Token::Operator("]"), .. }, inner)) = lexemes[1..].split_last()
+    else {
+        let last = lexemes.last().expect("a memory operand starts with `[`");
+        return Err(AssemblyError { line, column: last.column, kind: AssemblyErrorKind::UnclosedBracket });
+    };
+    let stack = match inner {
+        | [Lexeme { token: Token::Identifier(name), .. }, rest @ ..] if name.eq_ignore_ascii_case("sp") => {
+            Some((Operand::StackPointer, rest))
+        }
+        | _ => None,
+    };
+    let Some((base, rest)) = stack.map_or_else(|| parse_registers(line, inner), |stack| Ok(Some(stack)))?
+    else {
+        let column = inner.first().map_or(lexemes[0].column + 1, |lexeme| lexeme.column);
+        return Err(AssemblyError { line, column, kind: AssemblyErrorKind::ExpectedRegister });
+    };
+    let offset = match rest {
+        | [] => Expression::Value(0),
+        | [Lexeme { token: Token::Operator("+"), .. }, offset @ ..] => expression::parse(line, offset)?,
+        | [Lexeme { token: Token::Operator("-"), .. }, ..] => expression::parse(line, rest)?,
+        | [lexeme, ..] => return Err(unexpected(line, lexeme)),
+    };
+    Ok(Operand::Memory(Box::new(base), offset))
+}
+
+/// reads a leading `r2` or `r2:r3`, returning what follows it
+fn parse_registers(line: usize, lexemes: &[Lexeme]) -> Result<Option<(Operand, &[Lexeme])>, AssemblyError> {
+    let register = |lexeme: &Lexeme| match &lexeme.token {
+        | Token::Identifier(name) => register_index(name),
+        | _ => None,
+    };
+    match lexemes {
+        | [high, Lexeme { token: Token::Colon, .. }, low, rest @ ..] if let Some(index) = register(high) => {
+            match register(low) {
+                | Some(next) if next == index + 1 => Ok(Some((Operand::Pair(index), rest))),
+                | Some(next) => {
+                    let kind = AssemblyErrorKind::UnpairedRegisters { high: index, low: next };
+                    Err(AssemblyError { line, column: high.column, kind })
+                }
+                | None => Err(AssemblyError { line, column: low.column, kind: AssemblyErrorKind::ExpectedRegister }),
+            }
+        }
+        | [first, rest @ ..] if let Some(index) = register(first) => Ok(Some((Operand::Register(index), rest))),
+        | _ => Ok(None),
+    }
+}
+
+pub fn register_index(name: &str) -> Option<usize> {
+    let digits = name.strip_prefix(['r', 'R'])?;
+    if digits.is_empty() || !digits.chars().all(|chr| chr.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+pub fn unexpected(line: usize, lexeme: &Lexeme) -> AssemblyError {
+    let found = match &lexeme.token {
+        | Token::Identifier(name) => name.clone(),
+        | Token::Number(value) => value.to_string(),
+        | Token::Text(text) => format!("{text:?}"),
+        | Token::Operator(operator) => operator.to_string(),
+        | Token::Comma => ",".into(),
+        | Token::Colon => ":".into(),
+    };
+    AssemblyError { line, column: lexeme.column, kind: AssemblyErrorKind::UnexpectedToken(found) }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assembler::expression::BinaryOperator;
+    use crate::assembler::expression::UnaryOperator;
+    use crate::assembler::lexer::tokenize;
+
+    use super::*;
+
+    fn parse(text: &str) -> Result<Vec<Statement>, AssemblyError> {
+        parse_line(1, &tokenize(1, text)?)
+    }
+
+    #[test]
+    fn registers_and_values() {
+        let arguments = vec![
+            Argument { operand: Operand::Register(3), column: 9 },
+            Argument { operand: Operand::Expression(Expression::Value(10)), column: 13 },
+        ];
+        let expected = Statement::Instruction { mnemonic: "LOADIMM".into(), column: 1, arguments };
+        assert!(parse("LOADIMM r3, 10").unwrap() == vec![expected]);
+    }
+
+    #[test]
+    fn blank_line() {
+        assert!(parse("   ; nothing here").unwrap().is_empty());
+    }
+
+    #[test]
+    fn label_and_reference() {
+        let statements = parse("loop: JUMP loop").unwrap();
+        assert!(statements[0] == Statement::Label { name: "loop".into(), column: 1 });
+        let arguments = vec![Argument { operand: Operand::Expression(Expression::Symbol("loop".into())), column: 12 }];
+        assert!(statements[1] == Statement::Instruction { mnemonic: "JUMP".into(), column: 7, arguments });
+    }
+
+    #[test]
+    fn directive() {
+        let arguments = vec![
+            Argument { operand: Operand::Text("ok".into()), column: 7 },
+            Argument { operand: Operand::Expression(Expression::Value(0)), column: 13 },
+        ];
+        assert!(
+            parse(".byte \"ok\", 0").unwrap()
+                == vec![Statement::Directive { name: ".byte".into(), column: 1, arguments }]
+        );
+    }
+
+    #[test]
+    fn expression_operand() {
+        let statements = parse("LOADIMM r0, buffer + 4, 1").unwrap();
+        let Statement::Instruction { arguments, .. } = &statements[0]
+        else {
+            panic!("expected an instruction");
+        };
+        let sum = Expression::Binary(
+            BinaryOperator::Add,
+            Box::new(Expression::Symbol("buffer".into())),
+            Box::new(Expression::Value(4)),
+        );
+        assert!(arguments[1] == Argument { operand: Operand::Expression(sum), column: 13 });
+        assert!(arguments.len() == 3);
+    }
+
+    #[test]
+    fn empty_operand() {
+        let error = parse("ADD r0,, r1").unwrap_err();
+        assert!(error.kind == AssemblyErrorKind::ExpectedOperand);
+        assert!(error.column == 8);
+    }
+
+    #[test]
+    fn register_as_label() {
+        let error = parse("r2: HALT").unwrap_err();
+        assert!(error.kind == AssemblyErrorKind::UnexpectedToken("r2".into()));
+    }
+
+    #[test]
+    fn memory_operands() {
+        let operands = |text| match &parse(text).unwrap()[0] {
+            | Statement::Instruction { arguments, .. } => {
+                arguments.iter().map(|argument| argument.operand.clone()).collect::<Vec<Operand>>()
+            }
+            | statement => panic!("expected an instruction, found {statement:?}"),
+        };
+        let negated = Expression::Unary(UnaryOperator::Negate, Box::new(Expression::Value(1)));
+        assert!(
+            operands("LOADIND r0, [r1]")[1] == Operand::Memory(Box::new(Operand::Register(1)), Expression::Value(0))
+        );
+        assert!(operands("STOREPAIR [r2:r3-1], r0")[0] == Operand::Memory(Box::new(Operand::Pair(2)), negated));
+        assert!(
+            operands("LOADIND r0, [r1+4]")[1] == Operand::Memory(Box::new(Operand::Register(1)), Expression::Value(4))
+        );
+        assert!(
+            operands("LOADSP r0, [SP]")[1] == Operand::Memory(Box::new(Operand::StackPointer), Expression::Value(0))
+        );
+        assert!(parse("LOADIND r0, [r1+4").unwrap_err().kind == AssemblyErrorKind::UnclosedBracket);
+        assert!(parse("LOADIND r0, [4]").unwrap_err().kind == AssemblyErrorKind::ExpectedRegister);
+        assert!(
+            parse("STOREPAIR [r2:r4], r0").unwrap_err().kind
+                == AssemblyErrorKind::UnpairedRegisters { high: 2, low: 4 }
+        );
+    }
+
+    #[test]
+    fn dangling_comma() {
+        let error = parse("PUSH r1,").unwrap_err();
+        assert!(error.kind == AssemblyErrorKind::ExpectedOperand);
+        assert!(error.column == 9);
+    }
+}
@@ -5,6 +5,7 @@ pub trait Cycle<Address, Data> {
 pub struct BusResponse<'d, Address, Data> {
     pub address: &'d mut Option<Address>,
     pub data: &'d mut Option<Data>,
+    pub error: &'d mut Option<Address>,
 }
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -20,6 +21,8 @@ pub struct Bus<Address, Data> {
     instruction: BusState,
     address: Option<Address>,
     data: Option<Data>,
+    /// address of the last dispatch nothing answered, left for the processor to pick up
+    error: Option<Address>,
 }
 
 impl<Address, Data> Bus<Address, Data> {
@@ -37,6 +40,18 @@ impl<Address, Data> Bus<Address, Data> {
         self.data.take()
     }
 
+    pub fn take_error(&mut self) -> Option<Address> {
+        self.error.take()
+    }
+
+    /// where the dispatch nobody has answered yet is headed, for devices that only answer part of the space
+    pub fn pending_address(&self) -> Option<Address>
+    where
+        Address: Copy,
+    {
+        self.address.filter(|_| self.instruction != BusState::Null)
+    }
+
     pub fn dispatch_read(&mut self, address: Address) -> Option<()> {
         assert!(self.is_avaliable());
         if !self.is_avaliable() {
@@ -60,9 +75,9 @@ impl<Address, Data> Bus<Address, Data> {
         Some(())
     }
 
-    pub fn complete_dispatch(&mut self) -> BusResponse<Address, Data> {
+    pub fn complete_dispatch(&mut self) -> BusResponse<'_, Address, Data> {
         self.instruction = BusState::Null;
-        BusResponse { address: &mut self.address, data: &mut self.data }
+        BusResponse { address: &mut self.address, data: &mut self.data, error: &mut self.error }
     }
 }
 
@@ -74,7 +89,7 @@ mod tests {
 
     #[test]
     fn reader_dispatch() {
-        let mut ram = MemoryBlock::<8, u8>::default();
+        let mut ram = MemoryBlock::<u8>::new(8);
         let mut bus = Bus::<u8, u8>::default();
         *ram.write(3_u8) = 33;
         bus.dispatch_read(3);
@@ -85,11 +100,21 @@ mod tests {
 
     #[test]
     fn writer_dispatch() {
-        let mut ram = MemoryBlock::<8, u8>::default();
+        let mut ram = MemoryBlock::<u8>::new(8);
         let mut bus = Bus::<u8, u8>::default();
         bus.dispatch_write(3, 33);
         ram.cycle(&mut bus);
         assert!(bus.instruction == BusState::Null);
         assert!(ram.read(3_u8) == 33);
     }
+
+    #[test]
+    fn unanswered_dispatch() {
+        let mut ram = MemoryBlock::<u8>::new(8);
+        let mut bus = Bus::<u8, u8>::default();
+        bus.dispatch_read(9);
+        ram.cycle(&mut bus);
+        assert!(bus.is_avaliable());
+        assert!(bus.take_error() == Some(9) && bus.take_error().is_none());
+    }
 }
@@ -0,0 +1,220 @@
+use crate::cpu::ADDRESS_SPACE;
+use crate::cpu::Data;
+use crate::cpu::Pointer;
+use crate::number;
+
+/// an address range set aside for a memory mapped device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceMapping {
+    pub name: String,
+    pub base: usize,
+    pub size: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    Syntax { line: usize, text: String },
+    UnknownKey { line: usize, key: String },
+    InvalidValue { line: usize, key: String, value: String },
+    OutOfRange { setting: &'static str, value: usize, limit: usize },
+    DeviceOverlap { first: String, second: String },
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, frmtr: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            | Self::Syntax { line, text } => write!(frmtr, "line {line}: expected `key = value`, found `{text}`"),
+            | Self::UnknownKey { line, key } => write!(frmtr, "line {line}: unknown setting `{key}`"),
+            | Self::InvalidValue { line, key, value } => {
+                write!(frmtr, "line {line}: `{value}` is not valid for `{key}`")
+            }
+            | Self::OutOfRange { setting, value, limit } => {
+                write!(frmtr, "{setting} {value:#x} must be below {limit:#x}")
+            }
+            | Self::DeviceOverlap { first, second } => write!(frmtr, "devices `{first}` and `{second}` overlap"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// everything that differs between board variants, read once before the machine is built
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MachineConfig {
+    pub ram_size: usize,
+    pub registers: usize,
+    /// overrides the entry point of a loaded image when set
+    pub program_counter: Option<Pointer>,
+    /// overrides the stack pointer of a loaded image when set, the top of ram otherwise
+    pub stack_pointer: Option<Pointer>,
+    /// the stack pointer of a full stack, the last byte of a loaded image when unset
+    pub stack_limit: Option<Pointer>,
+    /// handler entered on a fault with its code pushed, without one a fault halts
+    pub fault_vector: Option<Pointer>,
+    /// little endian handler addresses, two bytes per interrupt line, without one interrupts stay pending
+    pub interrupt_table: Option<Pointer>,
+    /// what reads from unmapped addresses return, without it they are a bus error
+    pub open_bus: Option<Data>,
+    pub cycle_limit: usize,
+    pub devices: Vec<DeviceMapping>,
+}
+
+impl Default for MachineConfig {
+    fn default() -> Self {
+        Self {
+            ram_size: 512,
+            registers: 8,
+            program_counter: None,
+            stack_pointer: None,
+            stack_limit: None,
+            fault_vector: None,
+            interrupt_table: None,
+            open_bus: None,
+            cycle_limit: 100_000_000,
+            devices: Vec::new(),
+        }
+    }
+}
+
+impl MachineConfig {
+    pub fn initial_program_counter(&self) -> Pointer {
+        self.program_counter.unwrap_or_default()
+    }
+
+    pub fn initial_stack_pointer(&self) -> Pointer {
+        self.stack_pointer.unwrap_or((self.ram_size - 1) as Pointer)
+    }
+
+    /// reads `key = value` lines on top of the defaults, `#` starts a comment
+    pub fn parse(text: &str) -> Result<Self, ConfigError> {
+        let mut config = Self::default();
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=').map(|(key, value)| (key.trim(), value.trim()))
+            else {
+                return Err(ConfigError::Syntax { line: line_number, text: line.into() });
+            };
+            config.set(key, value).map_err(|error| match error {
+                | ConfigError::InvalidValue { key, value, .. } => {
+                    ConfigError::InvalidValue { line: line_number, key, value }
+                }
+                | ConfigError::UnknownKey { key, .. } => ConfigError::UnknownKey { line: line_number, key },
+                | error => error,
+            })?;
+        }
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// applies one setting, shared by the file format and command line overrides
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        let invalid = || ConfigError::InvalidValue { line: 0, key: key.into(), value: value.into() };
+        let pointer = |value: &str| number::parse(value).ok_or_else(invalid);
+        match key {
+            | "ram" => self.ram_size = number::parse(value).ok_or_else(invalid)?,
+            | "registers" => self.registers = number::parse(value).ok_or_else(invalid)?,
+            | "pc" => self.program_counter = Some(pointer(value)?),
+            | "sp" => self.stack_pointer = Some(pointer(value)?),
+            | "stack_limit" => self.stack_limit = Some(pointer(value)?),
+            | "fault_vector" => self.fault_vector = Some(pointer(value)?),
+            | "interrupt_table" => self.interrupt_table = Some(pointer(value)?),
+            | "open_bus" => self.open_bus = Some(number::parse(value).ok_or_else(invalid)?),
+            | "cycles" => self.cycle_limit = number::parse(value).ok_or_else(invalid)?,
+            | "device" => {
+                let [name, base, size] = value.split_whitespace().collect::<Vec<&str>>()[..]
+                else {
+                    return Err(invalid());
+                };
+                let (base, size) = (number::parse(base).ok_or_else(invalid)?, number::parse(size).ok_or_else(invalid)?);
+                self.devices.push(DeviceMapping { name: name.into(), base, size });
+            }
+            | _ => return Err(ConfigError::UnknownKey { line: 0, key: key.into() }),
+        }
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let within = |setting, value: usize, limit: usize| match (1..=limit).contains(&value) {
+            | true => Ok(()),
+            | false => Err(ConfigError::OutOfRange { setting, value, limit: limit + 1 }),
+        };
+        within("ram", self.ram_size, ADDRESS_SPACE)?;
+        within("registers", self.registers, Data::MAX as usize + 1)?;
+        let pointers = [
+            ("pc", self.program_counter),
+            ("sp", self.stack_pointer),
+            ("stack_limit", self.stack_limit),
+            ("fault_vector", self.fault_vector),
+            ("interrupt_table", self.interrupt_table),
+        ];
+        for (setting, value) in pointers {
+            if let Some(value) = value.filter(|value| *value as usize >= self.ram_size) {
+                return Err(ConfigError::OutOfRange { setting, value: value as usize, limit: self.ram_size });
+            }
+        }
+
+        for device in &self.devices {
+            within("device size", device.size, ADDRESS_SPACE)?;
+            if device.base.checked_add(device.size).is_none_or(|end| end > ADDRESS_SPACE) {
+                let value = device.base.saturating_add(device.size);
+                return Err(ConfigError::OutOfRange { setting: "device end", value, limit: ADDRESS_SPACE + 1 });
+            }
+        }
+        // every device ends inside the address space by now, so the sums below cannot overflow
+        for (index, device) in self.devices.iter().enumerate() {
+            if let Some(other) = self.devices[index + 1..]
+                .iter()
+                .find(|other| device.base < other.base + other.size && other.base < device.base + device.size)
+            {
+                return Err(ConfigError::DeviceOverlap { first: device.name.clone(), second: other.name.clone() });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsed_file() {
+        let config = MachineConfig::parse(
+            "
+            # small board with a console
+            ram = 0x400
+            registers = 4
+            cycles = 5000
+            device = console 0xff00 16
+            open_bus = 0xff
+            ",
+        )
+        .unwrap();
+        assert!(config.ram_size == 1024 && config.registers == 4 && config.cycle_limit == 5000);
+        assert!(config.initial_stack_pointer() == 0x3FF && config.initial_program_counter() == 0);
+        assert!(config.devices == [DeviceMapping { name: "console".into(), base: 0xFF00, size: 16 }]);
+        assert!(config.open_bus == Some(0xFF));
+    }
+
+    #[test]
+    fn rejected_settings() {
+        let error = |text: &str| MachineConfig::parse(text).unwrap_err();
+        assert!(error("ram 512") == ConfigError::Syntax { line: 1, text: "ram 512".into() });
+        assert!(error("\nspeed = 4") == ConfigError::UnknownKey { line: 2, key: "speed".into() });
+        assert!(
+            error("pc = 0x10000") == ConfigError::InvalidValue { line: 1, key: "pc".into(), value: "0x10000".into() }
+        );
+        assert!(error("ram = 256\nsp = 256") == ConfigError::OutOfRange { setting: "sp", value: 256, limit: 256 });
+        assert!(error("registers = 0") == ConfigError::OutOfRange { setting: "registers", value: 0, limit: 257 });
+        assert!(
+            error("device = a 0x10 8\ndevice = b 0x14 4")
+                == ConfigError::DeviceOverlap { first: "a".into(), second: "b".into() }
+        );
+        let end = ConfigError::OutOfRange { setting: "device end", value: usize::MAX, limit: ADDRESS_SPACE + 1 };
+        assert!(error("device = a 0x10 8\ndevice = b 0xffffffffffffffff 8") == end);
+    }
+}
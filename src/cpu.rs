@@ -1,23 +1,33 @@
-use crate::CYCLE_LIMIT;
-use crate::RAM_SIZE;
 use crate::bus::Bus;
 use crate::bus::Cycle;
-use crate::clock::Clock;
+use crate::config::MachineConfig;
 use crate::instructions;
 use crate::instructions::Instruction;
+use crate::instructions::OperandKind;
+use crate::instructions::arithmetic::Outcome;
 use crate::memory::Addressable;
 use crate::memory::MemoryBlock;
 
 pub type Data = u8;
 pub type Pointer = u16;
 
-type RegisterArray<const R: usize, Data> = MemoryBlock<R, Data>;
+pub const ADDRESS_SPACE: usize = 1 << Pointer::BITS;
+
+/// operands the longest instruction carries
+const OPERAND_CAPACITY: usize = 4;
+
+type RegisterArray<Data> = MemoryBlock<Data>;
 
 #[derive(Debug, Default)]
 pub struct ProcFlags {
     pub zero: bool,
     pub less: bool,
     pub great: bool,
+    pub carry: bool,
+    pub overflow: bool,
+    pub negative: bool,
+    /// lets the processor take interrupts, cleared while one is handled
+    pub interrupt_enable: bool,
     pub complete: bool,
 }
 
@@ -30,21 +40,128 @@ impl ProcFlags {
         self.zero = Default::default();
         self.less = Default::default();
         self.great = Default::default();
+        self.carry = Default::default();
+        self.overflow = Default::default();
+        self.negative = Default::default();
+    }
+
+    /// the byte an interrupt pushes so `reti` can put every flag back
+    pub fn to_byte(&self) -> Data {
+        [self.zero, self.less, self.great, self.carry, self.overflow, self.negative, self.interrupt_enable]
+            .into_iter()
+            .enumerate()
+            .fold(0, |byte, (bit, set)| byte | (set as Data) << bit)
+    }
+
+    pub fn load_byte(&mut self, byte: Data) {
+        let bit = |index: u32| byte & (1 << index) != 0;
+        self.zero = bit(0);
+        self.less = bit(1);
+        self.great = bit(2);
+        self.carry = bit(3);
+        self.overflow = bit(4);
+        self.negative = bit(5);
+        self.interrupt_enable = bit(6);
+    }
+
+    /// arithmetic leaves zero, less and great to `compare`
+    pub fn set_arithmetic(&mut self, outcome: Outcome) {
+        self.carry = outcome.carry;
+        self.overflow = outcome.overflow;
+        self.negative = outcome.negative();
     }
 }
 
-#[derive(Debug, Default)]
-pub struct OperandBuffer<const N: usize, Data>
+/// architectural faults, raised instead of panicking the host
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultKind {
+    InvalidOpcode(Data),
+    DivideByZero,
+    InvalidRegister(Data),
+    BusError(Pointer),
+    StackOverflow,
+    StackUnderflow,
+    InvalidSyscall(Data),
+    SyscallFailed(Data),
+}
+
+impl FaultKind {
+    /// the byte a guest fault handler finds on top of its stack
+    pub fn code(&self) -> Data {
+        match self {
+            | FaultKind::InvalidOpcode(_) => 1,
+            | FaultKind::DivideByZero => 2,
+            | FaultKind::InvalidRegister(_) => 3,
+            | FaultKind::BusError(_) => 4,
+            | FaultKind::StackOverflow => 5,
+            | FaultKind::StackUnderflow => 6,
+            | FaultKind::InvalidSyscall(_) => 7,
+            | FaultKind::SyscallFailed(_) => 8,
+        }
+    }
+}
+
+impl std::fmt::Display for FaultKind {
+    fn fmt(&self, frmtr: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            | FaultKind::InvalidOpcode(opcode) => write!(frmtr, "invalid opcode {opcode:#04x}"),
+            | FaultKind::DivideByZero => write!(frmtr, "division by zero"),
+            | FaultKind::InvalidRegister(index) => write!(frmtr, "register r{index} does not exist"),
+            | FaultKind::BusError(address) => write!(frmtr, "nothing answers at {address:#06x}"),
+            | FaultKind::StackOverflow => write!(frmtr, "stack overflow"),
+            | FaultKind::StackUnderflow => write!(frmtr, "stack underflow"),
+            | FaultKind::InvalidSyscall(number) => write!(frmtr, "no handler for syscall {number}"),
+            | FaultKind::SyscallFailed(number) => write!(frmtr, "syscall {number} failed"),
+        }
+    }
+}
+
+/// a fault and the address of the instruction that raised it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fault {
+    pub kind: FaultKind,
+    pub program_counter: Pointer,
+}
+
+impl std::fmt::Display for Fault {
+    fn fmt(&self, frmtr: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(frmtr, "{} at {:#06x}", self.kind, self.program_counter)
+    }
+}
+
+impl std::error::Error for Fault {}
+
+/// an active call as the processor saw it, kept beside the guest stack for backtraces
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    pub call_site: Pointer,
+    pub target: Pointer,
+    pub return_address: Pointer,
+    /// the stack pointer just below the pushed return address, the frame is gone once the stack pops above it
+    pub stack_pointer: Pointer,
+}
+
+#[derive(Debug)]
+pub struct OperandBuffer<Data>
 where
     Data: Copy,
 {
-    operands: MemoryBlock<N, Option<Data>>,
+    operands: MemoryBlock<Option<Data>>,
     required: usize,
     fetched: usize,
     reader_head: usize,
 }
 
-impl<const N: usize, Data> OperandBuffer<N, Data>
+impl<Data> Default for OperandBuffer<Data>
+where
+    Data: Copy,
+{
+    fn default() -> Self {
+        Self { operands: MemoryBlock::new(OPERAND_CAPACITY), required: 0, fetched: 0, reader_head: 0 }
+    }
+}
+
+impl<Data> OperandBuffer<Data>
 where
     Data: Default + Copy,
 {
@@ -66,6 +183,10 @@ where
     pub fn reset(&mut self) {
         *self = Self::default();
     }
+
+    pub fn fetched(&self) -> impl Iterator<Item = Data> + '_ {
+        (0..self.fetched).filter_map(|index| self.operands.read(index))
+    }
 }
 
 #[derive(Debug, Default)]
@@ -77,6 +198,8 @@ pub enum ProcState {
     FetchOperands,
     Execute,
     WriteBack,
+    Trap(Fault),
+    Interrupt(Data),
 }
 
 #[derive(Debug, Default)]
@@ -94,22 +217,30 @@ impl MicroState {
     }
 }
 
-fn procstate_idle<const R: usize>(cpu: &mut Processor<R>, bus: &mut Bus<Pointer, Data>) {
+fn procstate_idle(cpu: &mut Processor, bus: &mut Bus<Pointer, Data>) {
+    cpu.instruction_address = cpu.program_counter;
+    if let Some(line) = cpu.interrupt_request.filter(|_| cpu.flags.interrupt_enable && cpu.interrupt_table.is_some()) {
+        cpu.microstate.reset();
+        cpu.state = ProcState::Interrupt(line);
+        return;
+    }
     cpu.initiate_fetch(bus);
     cpu.state = ProcState::FetchInit;
 }
 
-fn procstate_fetch_init<const R: usize>(cpu: &mut Processor<R>, bus: &mut Bus<Pointer, Data>) {
-    let Some(instruction) = bus.read_data()
+fn procstate_fetch_init(cpu: &mut Processor, bus: &mut Bus<Pointer, Data>) {
+    let Some(opcode) = bus.read_data()
     else {
         return;
     };
-
-    cpu.current_instruction = instruction.into();
+    match Instruction::try_from(opcode) {
+        | Ok(instruction) => cpu.current_instruction = instruction,
+        | Err(kind) => return cpu.raise(kind),
+    }
     cpu.state = ProcState::Decode;
 }
 
-fn procstate_decode<const R: usize>(cpu: &mut Processor<R>) {
+fn procstate_decode(cpu: &mut Processor) {
     cpu.operand_buffer.reset();
     cpu.microstate.reset();
     cpu.flags.reset_complete();
@@ -117,7 +248,7 @@ fn procstate_decode<const R: usize>(cpu: &mut Processor<R>) {
     cpu.state = ProcState::FetchOperands;
 }
 
-fn procstate_fetch_operands<const R: usize>(cpu: &mut Processor<R>, bus: &mut Bus<Pointer, Data>) {
+fn procstate_fetch_operands(cpu: &mut Processor, bus: &mut Bus<Pointer, Data>) {
     if let Some(operand) = bus.read_data() {
         cpu.operand_buffer.push(operand);
     }
@@ -126,90 +257,235 @@ fn procstate_fetch_operands<const R: usize>(cpu: &mut Processor<R>, bus: &mut Bu
         cpu.initiate_fetch(bus);
         return;
     }
+
+    let registers = cpu.registers.size();
+    let kinds = cpu.current_instruction.operand_kinds();
+    let invalid = kinds.iter().zip(cpu.operand_buffer.fetched()).find_map(|(kind, index)| match kind {
+        | OperandKind::Register if index as usize >= registers => Some(index),
+        | OperandKind::Pair if index as usize + 1 >= registers => Some(index.wrapping_add(1)),
+        | _ => None,
+    });
+    if let Some(index) = invalid {
+        return cpu.raise(FaultKind::InvalidRegister(index));
+    }
     cpu.state = ProcState::Execute;
 }
 
-fn procstate_execute<const R: usize>(cpu: &mut Processor<R>, bus: &mut Bus<Pointer, Data>) {
+fn procstate_execute(cpu: &mut Processor, bus: &mut Bus<Pointer, Data>) {
     if !cpu.flags.complete {
         cpu.execute(bus);
+        if cpu.flags.complete && matches!(cpu.state, ProcState::Execute) {
+            cpu.retired += 1;
+        }
         return;
     }
 
     cpu.state = ProcState::WriteBack;
 }
 
-fn procstate_writeback<const R: usize>(cpu: &mut Processor<R>) {
+fn procstate_writeback(cpu: &mut Processor) {
     cpu.current_instruction = Instruction::Null;
     cpu.state = ProcState::Idle;
 }
 
+/// pushes the address of the faulting instruction like `call` and then the fault code, and enters the handler,
+/// a fault with nowhere to push halts
+fn procstate_trap(cpu: &mut Processor, bus: &mut Bus<Pointer, Data>, fault: Fault) {
+    let return_address = fault.program_counter;
+    let room = (cpu.stack_pointer as usize) >= cpu.stack_limit as usize + 3;
+    let Some(vector) = cpu.fault_vector.filter(|_| room || !matches!(cpu.microstate, MicroState(0)))
+    else {
+        cpu.fault = Some(fault);
+        cpu.halted = true;
+        return;
+    };
+
+    assert!(bus.is_avaliable());
+    match cpu.microstate {
+        | MicroState(0) => {
+            bus.dispatch_write(cpu.stack_pointer, (return_address >> 8) as Data);
+        }
+        | MicroState(1) => {
+            bus.dispatch_write(cpu.stack_pointer, return_address as Data);
+        }
+        | _ => {
+            bus.dispatch_write(cpu.stack_pointer, fault.kind.code());
+            let stack_pointer = cpu.stack_pointer;
+            cpu.call_stack.push(Frame { call_site: return_address, target: vector, return_address, stack_pointer });
+            cpu.program_counter = vector;
+            cpu.state = ProcState::Idle;
+        }
+    }
+    cpu.stack_pointer -= 1;
+    cpu.microstate.increment();
+}
+
+/// pushes the return address like `call` and then the flags, and enters the handler the table names for `line`
+fn procstate_interrupt(cpu: &mut Processor, bus: &mut Bus<Pointer, Data>, line: Data) {
+    let return_address = cpu.program_counter;
+    let entry = cpu.interrupt_table.unwrap_or_default().wrapping_add(2 * line as Pointer);
+    match cpu.microstate {
+        | MicroState(0) => {
+            if (cpu.stack_pointer as usize) < cpu.stack_limit as usize + 3 {
+                return cpu.raise(FaultKind::StackOverflow);
+            }
+            assert!(bus.is_avaliable());
+            bus.dispatch_write(cpu.stack_pointer, (return_address >> 8) as Data);
+            cpu.stack_pointer -= 1;
+            cpu.interrupt_acknowledge = Some(line);
+            cpu.microstate.increment();
+        }
+        | MicroState(1) => {
+            assert!(bus.is_avaliable());
+            bus.dispatch_write(cpu.stack_pointer, return_address as Data);
+            cpu.stack_pointer -= 1;
+            cpu.microstate.increment();
+        }
+        | MicroState(2) => {
+            assert!(bus.is_avaliable());
+            bus.dispatch_write(cpu.stack_pointer, cpu.flags.to_byte());
+            cpu.stack_pointer -= 1;
+            cpu.flags.interrupt_enable = false;
+            cpu.microstate.increment();
+        }
+        | MicroState(3) => {
+            assert!(bus.is_avaliable());
+            bus.dispatch_read(entry);
+            cpu.microstate.increment();
+        }
+        | MicroState(4) => {
+            if let Some(data) = bus.read_data() {
+                cpu.interrupt_entry = data as Pointer;
+                bus.dispatch_read(entry.wrapping_add(1));
+                cpu.microstate.increment();
+            }
+        }
+        | _ => {
+            if let Some(data) = bus.read_data() {
+                let target = cpu.interrupt_entry | (data as Pointer) << 8;
+                let stack_pointer = cpu.stack_pointer + 1;
+                cpu.call_stack.push(Frame {
+                    call_site: cpu.instruction_address,
+                    target,
+                    return_address,
+                    stack_pointer,
+                });
+                cpu.program_counter = target;
+                cpu.state = ProcState::Idle;
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
-pub struct Processor<const R: usize> {
+pub struct Processor {
     pub program_counter: Pointer,
     pub stack_pointer: Pointer,
-    pub registers: RegisterArray<R, Data>,
+    pub registers: RegisterArray<Data>,
     pub flags: ProcFlags,
     pub halted: bool,
     pub state: ProcState,
     pub microstate: MicroState,
     pub current_instruction: Instruction,
-    pub operand_buffer: OperandBuffer<R, Data>,
+    pub operand_buffer: OperandBuffer<Data>,
+    /// where the instruction being run starts, reported with its faults
+    pub instruction_address: Pointer,
+    /// the stack pointer of an empty stack, popping past it underflows
+    pub stack_base: Pointer,
+    /// the stack pointer of a full stack, pushing past it overflows
+    pub stack_limit: Pointer,
+    pub fault_vector: Option<Pointer>,
+    /// the fault that halted the processor, faults taken by a handler are not kept
+    pub fault: Option<Fault>,
+    /// instructions that ran to completion since power on
+    pub retired: usize,
+    /// calls not yet returned from, innermost last
+    pub call_stack: Vec<Frame>,
+    pub interrupt_table: Option<Pointer>,
+    /// the line the interrupt controller wants served, sampled between instructions
+    pub interrupt_request: Option<Data>,
+    /// the line the processor started serving, for the controller to clear
+    pub interrupt_acknowledge: Option<Data>,
+    /// low byte of the handler address while it is read from the table
+    interrupt_entry: Pointer,
+    /// the service a `syscall` asked for, taken and served by whoever embeds the processor
+    pub syscall_request: Option<Data>,
 }
 
-impl<const R: usize> Default for Processor<R> {
+impl Default for Processor {
     fn default() -> Self {
+        Self::new(&MachineConfig::default())
+    }
+}
+
+impl Processor {
+    pub fn new(config: &MachineConfig) -> Self {
         Self {
-            program_counter: Default::default(),
-            stack_pointer: (RAM_SIZE - 1) as Pointer,
-            registers: Default::default(),
+            program_counter: config.initial_program_counter(),
+            stack_pointer: config.initial_stack_pointer(),
+            registers: RegisterArray::new(config.registers),
             flags: Default::default(),
             halted: Default::default(),
             state: Default::default(),
             microstate: MicroState::default(),
             current_instruction: Default::default(),
             operand_buffer: Default::default(),
+            instruction_address: Default::default(),
+            stack_base: config.initial_stack_pointer(),
+            stack_limit: config.stack_limit.unwrap_or_default(),
+            fault_vector: config.fault_vector,
+            fault: Default::default(),
+            retired: Default::default(),
+            call_stack: Default::default(),
+            interrupt_table: config.interrupt_table,
+            interrupt_request: Default::default(),
+            interrupt_acknowledge: Default::default(),
+            interrupt_entry: Default::default(),
+            syscall_request: Default::default(),
         }
     }
-}
-
-pub fn _processor_run_debug<const M: usize, const R: usize>(
-    cpu: &mut Processor<R>,
-    ram: &mut MemoryBlock<M, Data>,
-    bus: &mut Bus<Pointer, Data>,
-    clock: &mut Clock,
-) {
-    while !cpu.halted && clock.tick < CYCLE_LIMIT {
-        let ms_wait = 25;
-
-        println!("\x1b[2J\x1b[0H{:?}\n{:?}\n{:?}\n{:?}", &ram, &cpu, &bus, &clock);
-        std::thread::sleep(std::time::Duration::from_millis(ms_wait));
 
-        ram.cycle(bus);
+    pub fn register(&self, index: Data) -> Result<Data, FaultKind> {
+        self.registers.try_read(index as usize).ok_or(FaultKind::InvalidRegister(index))
+    }
 
-        println!("\x1b[2J\x1b[0H{:?}\n{:?}\n{:?}\n{:?}", &ram, &cpu, &bus, &clock);
-        std::thread::sleep(std::time::Duration::from_millis(ms_wait));
+    pub fn set_register(&mut self, index: Data, value: Data) -> Result<(), FaultKind> {
+        *self.registers.try_write(index as usize).ok_or(FaultKind::InvalidRegister(index))? = value;
+        Ok(())
+    }
 
-        cpu.cycle(bus);
+    /// reads `high` and the register after it as one pointer
+    pub fn read_pair(&self, high: Data) -> Result<Pointer, FaultKind> {
+        Ok(Pointer::from_be_bytes([self.register(high)?, self.register(high.wrapping_add(1))?]))
+    }
 
-        clock.tick += 1;
+    pub fn write_pair(&mut self, high: Data, value: Pointer) -> Result<(), FaultKind> {
+        let [upper, lower] = value.to_be_bytes();
+        self.register(high.wrapping_add(1))?;
+        self.set_register(high, upper)?;
+        self.set_register(high.wrapping_add(1), lower)
     }
-}
 
-pub fn processor_run<const M: usize, const R: usize>(
-    cpu: &mut Processor<R>,
-    ram: &mut MemoryBlock<M, Data>,
-    bus: &mut Bus<Pointer, Data>,
-    clock: &mut Clock,
-) {
-    while !cpu.halted && clock.tick < CYCLE_LIMIT {
-        cpu.cycle(bus);
-        ram.cycle(bus);
-        clock.tick += 1;
+    /// abandons the current instruction, entering the fault handler when there is one and halting otherwise
+    pub fn raise(&mut self, kind: FaultKind) {
+        let fault = Fault { kind, program_counter: self.instruction_address };
+        self.current_instruction = Instruction::Null;
+        self.flags.complete = true;
+        self.microstate.reset();
+        self.state = ProcState::Trap(fault);
+        if self.fault_vector.is_none() {
+            self.fault = Some(fault);
+            self.halted = true;
+        }
     }
 }
 
-impl<const R: usize> Cycle<Pointer, Data> for Processor<R> {
+impl Cycle<Pointer, Data> for Processor {
     fn cycle(&mut self, bus: &mut Bus<Pointer, Data>) {
+        if let Some(address) = bus.take_error() {
+            return self.raise(FaultKind::BusError(address));
+        }
+
         match self.state {
             | ProcState::Idle => procstate_idle(self, bus),
             | ProcState::FetchInit => procstate_fetch_init(self, bus),
@@ -217,37 +493,107 @@ impl<const R: usize> Cycle<Pointer, Data> for Processor<R> {
             | ProcState::FetchOperands => procstate_fetch_operands(self, bus),
             | ProcState::Execute => procstate_execute(self, bus),
             | ProcState::WriteBack => procstate_writeback(self),
+            | ProcState::Trap(fault) => procstate_trap(self, bus, fault),
+            | ProcState::Interrupt(line) => procstate_interrupt(self, bus, line),
         }
     }
 }
 
-impl<const R: usize> Processor<R> {
+impl Processor {
     fn initiate_fetch(&mut self, bus: &mut Bus<Pointer, Data>) {
         assert!(bus.is_avaliable());
         bus.dispatch_read(self.program_counter);
-        self.program_counter += 1;
+        self.program_counter = self.program_counter.wrapping_add(1);
     }
 
+    /// runs one cycle of the current instruction, a fault it reports is raised here
     fn execute(&mut self, bus: &mut Bus<Pointer, Data>) {
-        match self.current_instruction {
+        let outcome = match self.current_instruction {
             | Instruction::Halt => instructions::halt(self),
             | Instruction::Ret => instructions::ret(self, bus),
+            | Instruction::Call => instructions::call(self, bus),
+            | Instruction::CallReg => instructions::call_reg(self, bus),
+            | Instruction::CallWide => instructions::call_wide(self, bus),
+            | Instruction::And => instructions::and(self),
+            | Instruction::Or => instructions::or(self),
+            | Instruction::Xor => instructions::xor(self),
+            | Instruction::Not => instructions::not(self),
+            | Instruction::ShiftLeft => instructions::shift_left(self),
+            | Instruction::ShiftRight => instructions::shift_right(self),
+            | Instruction::ShiftRightArithmetic => instructions::shift_right_arithmetic(self),
+            | Instruction::RotateLeft => instructions::rotate_left(self),
+            | Instruction::RotateRight => instructions::rotate_right(self),
+            | Instruction::BitTest => instructions::bit_test(self),
+            | Instruction::BitSet => instructions::bit_set(self),
+            | Instruction::BitClear => instructions::bit_clear(self),
             | Instruction::LoadImm => instructions::load_imm(self),
             | Instruction::LoadMem => instructions::load_mem(self, bus),
+            | Instruction::Store => instructions::store(self, bus),
+            | Instruction::LoadIndirect => instructions::load_indirect(self, bus),
+            | Instruction::StoreIndirect => instructions::store_indirect(self, bus),
+            | Instruction::LoadPair => instructions::load_pair(self, bus),
+            | Instruction::StorePair => instructions::store_pair(self, bus),
+            | Instruction::LoadImmWide => instructions::load_imm_wide(self),
+            | Instruction::AddWide => instructions::add_wide(self),
+            | Instruction::SubWide => instructions::sub_wide(self),
+            | Instruction::IncrementWide => instructions::increment_wide(self),
+            | Instruction::DecrementWide => instructions::decrement_wide(self),
+            | Instruction::CompareWide => instructions::compare_wide(self),
+            | Instruction::GetStackPointer => instructions::get_stack_pointer(self),
+            | Instruction::SetStackPointer => instructions::set_stack_pointer(self),
+            | Instruction::GetProgramCounter => instructions::get_program_counter(self),
+            | Instruction::AdjustStack => instructions::adjust_stack(self),
+            | Instruction::LoadStack => instructions::load_stack(self, bus),
+            | Instruction::StoreStack => instructions::store_stack(self, bus),
+            | Instruction::SetStack => instructions::set_stack(self),
+            | Instruction::EnableInterrupts => instructions::enable_interrupts(self),
+            | Instruction::DisableInterrupts => instructions::disable_interrupts(self),
+            | Instruction::ReturnFromInterrupt => instructions::return_from_interrupt(self, bus),
+            | Instruction::Syscall => instructions::syscall(self),
             | Instruction::Copy => instructions::copy(self),
             | Instruction::Add => instructions::add(self),
             | Instruction::Sub => instructions::sub(self),
             | Instruction::Mul => instructions::mul(self),
             | Instruction::Div => instructions::div(self),
             | Instruction::Jump => instructions::jump(self),
+            | Instruction::JumpWide => instructions::jump_wide(self),
             | Instruction::JumpIfZero => instructions::jump_if_zero(self),
             | Instruction::Push => instructions::push(self, bus),
             | Instruction::Pop => instructions::pop(self, bus),
             | Instruction::Compare => instructions::compare(self),
             | Instruction::Increment => instructions::increment(self),
             | Instruction::Decrement => instructions::decrement(self),
-            | Instruction::Null => {}
-            | Instruction::EnumLength => panic!("this can only be explained by corrupt bytes"),
+            | Instruction::Null => Ok(()),
+            | Instruction::EnumLength => Err(FaultKind::InvalidOpcode(Instruction::EnumLength.into())),
+            | Instruction::JumpEqual
+            | Instruction::JumpNotEqual
+            | Instruction::JumpLess
+            | Instruction::JumpGreater
+            | Instruction::JumpLessEqual
+            | Instruction::JumpGreaterEqual
+            | Instruction::JumpCarry
+            | Instruction::JumpOverflow
+            | Instruction::JumpNegative
+            | Instruction::JumpLessSigned
+            | Instruction::JumpGreaterSigned
+            | Instruction::JumpLessEqualSigned
+            | Instruction::JumpGreaterEqualSigned => match self.current_instruction.condition() {
+                | Some(condition) => instructions::jump_when(self, condition),
+                | None => Err(FaultKind::InvalidOpcode(self.current_instruction.into())),
+            },
+        };
+        if let Err(kind) = outcome {
+            self.raise(kind);
+        }
+        if self.flags.complete {
+            self.unwind_call_stack();
         }
     }
+
+    /// drops the frames whose return address the stack has popped, so `call_stack` follows the guest stack
+    /// through `ret`, `reti`, pops and any direct write to the stack pointer
+    fn unwind_call_stack(&mut self) {
+        let live = self.call_stack.partition_point(|frame| frame.stack_pointer >= self.stack_pointer);
+        self.call_stack.truncate(live);
+    }
 }
@@ -0,0 +1,216 @@
+use crate::bus::Bus;
+use crate::bus::BusState;
+use crate::cpu::ADDRESS_SPACE;
+use crate::cpu::Data;
+use crate::cpu::Pointer;
+
+/// one entry of the memory map, addresses inside it reach `device` relative to `base`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub name: String,
+    pub base: usize,
+    pub size: usize,
+    pub device: usize,
+}
+
+impl Region {
+    pub fn new(name: &str, base: usize, size: usize, device: usize) -> Self {
+        Self { name: name.into(), base, size, device }
+    }
+
+    /// one past the last address, `None` when that does not fit in a `usize`
+    fn end(&self) -> Option<usize> {
+        self.base.checked_add(self.size)
+    }
+
+    fn contains(&self, address: usize) -> bool {
+        address.checked_sub(self.base).is_some_and(|offset| offset < self.size)
+    }
+
+    /// an end that overflows reaches past every address
+    fn overlaps(&self, other: &Region) -> bool {
+        other.end().is_none_or(|end| self.base < end) && self.end().is_none_or(|end| other.base < end)
+    }
+}
+
+/// how the decoder answers an address no region covers
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Unmapped {
+    /// the processor takes a bus error fault
+    #[default]
+    BusError,
+    /// reads float to this value and writes are dropped
+    OpenBus(Data),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MapError {
+    Overlap {
+        first: String,
+        second: String,
+    },
+    OutOfRange {
+        name: String,
+        end: usize,
+    },
+    Empty {
+        name: String,
+    },
+    /// the config maps a device the machine does not have
+    UnknownDevice {
+        name: String,
+    },
+}
+
+impl std::fmt::Display for MapError {
+    fn fmt(&self, frmtr: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            | Self::Overlap { first, second } => write!(frmtr, "`{first}` and `{second}` overlap in the memory map"),
+            | Self::OutOfRange { name, end } => {
+                write!(frmtr, "`{name}` ends at {end:#x}, past the {ADDRESS_SPACE:#x} byte address space")
+            }
+            | Self::Empty { name } => write!(frmtr, "`{name}` covers no addresses"),
+            | Self::UnknownDevice { name } => write!(frmtr, "no device called `{name}` to map"),
+        }
+    }
+}
+
+impl std::error::Error for MapError {}
+
+/// splits the address space between devices, each transaction goes to the one region that covers it
+#[derive(Debug, Default)]
+pub struct AddressDecoder {
+    regions: Vec<Region>,
+    unmapped: Unmapped,
+}
+
+impl AddressDecoder {
+    /// builds the whole map at once, refusing regions that overlap or leave the address space
+    pub fn build(regions: impl IntoIterator<Item = Region>, unmapped: Unmapped) -> Result<Self, MapError> {
+        let mut decoder = Self { regions: Vec::new(), unmapped };
+        for region in regions {
+            decoder.map(region)?;
+        }
+        Ok(decoder)
+    }
+
+    pub fn map(&mut self, region: Region) -> Result<(), MapError> {
+        if region.size == 0 {
+            return Err(MapError::Empty { name: region.name });
+        }
+        if region.end().is_none_or(|end| end > ADDRESS_SPACE) {
+            return Err(MapError::OutOfRange { end: region.base.saturating_add(region.size), name: region.name });
+        }
+        if let Some(other) = self.regions.iter().find(|other| other.overlaps(&region)) {
+            return Err(MapError::Overlap { first: other.name.clone(), second: region.name });
+        }
+        let position = self.regions.partition_point(|other| other.base < region.base);
+        self.regions.insert(position, region);
+        Ok(())
+    }
+
+    pub fn regions(&self) -> &[Region] {
+        &self.regions
+    }
+
+    /// the region covering `address` and the offset into it
+    pub fn decode(&self, address: Pointer) -> Option<(&Region, Pointer)> {
+        let address = address as usize;
+        let region = self.regions.iter().find(|region| region.contains(address))?;
+        Some((region, (address - region.base) as Pointer))
+    }
+
+    /// hands the pending transaction to `answer` on a bus of its own with the address made relative to the region,
+    /// then copies the reply back, a device that leaves the transaction unanswered is a bus error
+    pub fn cycle(&self, bus: &mut Bus<Pointer, Data>, mut answer: impl FnMut(usize, &mut Bus<Pointer, Data>)) {
+        let Some(address) = bus.pending_address()
+        else {
+            return;
+        };
+        let instruction = bus.get_instruction();
+        let bus_state = bus.complete_dispatch();
+        bus_state.address.take();
+        let written = bus_state.data.take();
+
+        let Some((region, offset)) = self.decode(address)
+        else {
+            match (self.unmapped, instruction) {
+                | (Unmapped::OpenBus(value), BusState::Read) => *bus_state.data = Some(value),
+                | (Unmapped::OpenBus(_), _) => {}
+                | (Unmapped::BusError, _) => *bus_state.error = Some(address),
+            }
+            return;
+        };
+
+        let mut local = Bus::<Pointer, Data>::default();
+        match (instruction, written) {
+            | (BusState::Write, Some(data)) => local.dispatch_write(offset, data),
+            | _ => local.dispatch_read(offset),
+        };
+        answer(region.device, &mut local);
+        if local.pending_address().is_some() || local.take_error().is_some() {
+            *bus_state.error = Some(address);
+            return;
+        }
+        *bus_state.data = local.read_data();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bus::Cycle;
+    use crate::memory::Addressable;
+    use crate::memory::MemoryBlock;
+
+    use super::*;
+
+    #[test]
+    fn overlapping_regions() {
+        let regions = [Region::new("ram", 0, 0x100, 0), Region::new("port", 0x80, 4, 1)];
+        let error = AddressDecoder::build(regions, Unmapped::BusError).unwrap_err();
+        assert!(error == MapError::Overlap { first: "ram".into(), second: "port".into() });
+        let error = AddressDecoder::build([Region::new("top", 0xFFFF, 2, 0)], Unmapped::BusError).unwrap_err();
+        assert!(error == MapError::OutOfRange { name: "top".into(), end: 0x10001 });
+        assert!(AddressDecoder::build([Region::new("none", 0, 0, 0)], Unmapped::BusError).is_err());
+        let error = AddressDecoder::build([Region::new("wrap", usize::MAX, 2, 0)], Unmapped::BusError).unwrap_err();
+        assert!(error == MapError::OutOfRange { name: "wrap".into(), end: usize::MAX });
+        assert!(Region::new("wrap", usize::MAX, 2, 0).overlaps(&Region::new("top", usize::MAX - 1, 4, 1)));
+        assert!(!Region::new("wrap", usize::MAX, 2, 0).contains(0));
+    }
+
+    #[test]
+    fn routed_transactions() {
+        let mut devices = [MemoryBlock::<Data>::new(16), MemoryBlock::<Data>::new(4)];
+        let regions = [Region::new("high", 0x100, 4, 1), Region::new("low", 0, 16, 0)];
+        let decoder = AddressDecoder::build(regions, Unmapped::BusError).unwrap();
+        assert!(decoder.regions()[0].name == "low" && decoder.decode(0x102) == Some((&decoder.regions()[1], 2)));
+
+        let mut bus = Bus::<Pointer, Data>::default();
+        bus.dispatch_write(0x102, 7);
+        decoder.cycle(&mut bus, |device, local| devices[device].cycle(local));
+        assert!(bus.is_avaliable() && devices[1].read(2_usize) == 7 && devices[0].read(2_usize) == 0);
+
+        bus.dispatch_read(0x102);
+        decoder.cycle(&mut bus, |device, local| devices[device].cycle(local));
+        assert!(bus.read_data() == Some(7));
+
+        bus.dispatch_read(0x20);
+        decoder.cycle(&mut bus, |device, local| devices[device].cycle(local));
+        assert!(bus.is_avaliable() && bus.take_error() == Some(0x20));
+
+        bus.dispatch_read(0x04);
+        decoder.cycle(&mut bus, |_, _| {});
+        assert!(bus.take_error() == Some(0x04));
+    }
+
+    #[test]
+    fn open_bus() {
+        let decoder = AddressDecoder::build([], Unmapped::OpenBus(0xFF)).unwrap();
+        let mut bus = Bus::<Pointer, Data>::default();
+        bus.dispatch_write(0x20, 1);
+        decoder.cycle(&mut bus, |_, _| unreachable!("nothing is mapped"));
+        bus.dispatch_read(0x20);
+        decoder.cycle(&mut bus, |_, _| unreachable!("nothing is mapped"));
+        assert!(bus.read_data() == Some(0xFF) && bus.take_error().is_none());
+    }
+}
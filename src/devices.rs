@@ -0,0 +1,162 @@
+use crate::bus::Bus;
+use crate::bus::BusState;
+use crate::bus::Cycle;
+use crate::cpu::Data;
+use crate::cpu::Pointer;
+use crate::memory::Addressable;
+use crate::memory::MemoryBlock;
+use std::cell::RefCell;
+use std::io::Read;
+use std::io::Write;
+use std::rc::Rc;
+
+/// anything the address decoder can route transactions to
+pub trait Device: Cycle<Pointer, Data> + std::fmt::Debug {}
+
+impl<T: Cycle<Pointer, Data> + std::fmt::Debug> Device for T {}
+
+/// name of the config device the console port is mapped at
+pub const CONSOLE: &str = "console";
+
+/// console register offsets
+pub const DATA: Pointer = 0;
+pub const STATUS: Pointer = 1;
+
+/// read only memory, its contents are fixed when it is built and a write is a bus error
+#[derive(Debug)]
+pub struct Rom {
+    contents: MemoryBlock<Data>,
+}
+
+impl Rom {
+    pub fn new(bytes: &[Data]) -> Self {
+        let mut contents = MemoryBlock::new(bytes.len());
+        for (address, byte) in bytes.iter().enumerate() {
+            *contents.write(address) = *byte;
+        }
+        Self { contents }
+    }
+
+    pub fn size(&self) -> usize {
+        self.contents.size()
+    }
+}
+
+impl Cycle<Pointer, Data> for Rom {
+    fn cycle(&mut self, bus: &mut Bus<Pointer, Data>) {
+        match bus.get_instruction() {
+            | BusState::Read => self.contents.cycle(bus),
+            | BusState::Write => {
+                let bus_state = bus.complete_dispatch();
+                bus_state.data.take();
+                *bus_state.error = bus_state.address.take();
+            }
+            | BusState::Null => {}
+        }
+    }
+}
+
+/// one console for the port and the console syscalls, so both take from the same input and write to the same output
+pub type SharedConsole = Rc<RefCell<Console>>;
+
+/// a character port, writing `DATA` prints a byte and reading it takes one from the input,
+/// `STATUS` reads 1 once the input has run out
+pub struct Console {
+    input: Box<dyn Read>,
+    output: Box<dyn Write>,
+    exhausted: bool,
+}
+
+impl std::fmt::Debug for Console {
+    fn fmt(&self, frmtr: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        frmtr.debug_struct("Console").field("exhausted", &self.exhausted).finish_non_exhaustive()
+    }
+}
+
+impl Console {
+    pub fn new(input: impl Read + 'static, output: impl Write + 'static) -> Self {
+        Self { input: Box::new(input), output: Box::new(output), exhausted: false }
+    }
+
+    pub fn shared(self) -> SharedConsole {
+        Rc::new(RefCell::new(self))
+    }
+
+    /// the next input byte, `None` once the input has run out
+    pub fn get(&mut self) -> std::io::Result<Option<Data>> {
+        let mut byte = [0];
+        let read = self.input.read(&mut byte);
+        self.exhausted = !matches!(read, Ok(1));
+        Ok((read? == 1).then_some(byte[0]))
+    }
+
+    pub fn put(&mut self, bytes: &[Data]) -> std::io::Result<()> {
+        self.output.write_all(bytes).and_then(|()| self.output.flush())
+    }
+
+    fn read(&mut self, offset: Pointer) -> Data {
+        match offset {
+            | DATA => self.get().ok().flatten().unwrap_or_default(),
+            | STATUS => self.exhausted as Data,
+            | _ => Default::default(),
+        }
+    }
+
+    /// output that cannot be written is dropped, the port has no way to report it
+    fn write(&mut self, offset: Pointer, data: Data) {
+        if offset == DATA {
+            let _ = self.put(&[data]);
+        }
+    }
+}
+
+impl Cycle<Pointer, Data> for Console {
+    fn cycle(&mut self, bus: &mut Bus<Pointer, Data>) {
+        match bus.get_instruction() {
+            | BusState::Read => {
+                let bus_state = bus.complete_dispatch();
+                if let Some(offset) = bus_state.address.take() {
+                    *bus_state.data = Some(self.read(offset));
+                }
+            }
+            | BusState::Write => {
+                let bus_state = bus.complete_dispatch();
+                if let (Some(offset), Some(data)) = (bus_state.address.take(), bus_state.data.take()) {
+                    self.write(offset, data);
+                }
+            }
+            | BusState::Null => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rom_rejects_writes() {
+        let mut rom = Rom::new(&[1, 2, 3]);
+        let mut bus = Bus::<Pointer, Data>::default();
+        bus.dispatch_read(2);
+        rom.cycle(&mut bus);
+        assert!(bus.read_data() == Some(3));
+        bus.dispatch_write(1, 9);
+        rom.cycle(&mut bus);
+        assert!(bus.is_avaliable() && bus.take_error() == Some(1));
+    }
+
+    #[test]
+    fn console_port() {
+        let mut console = Console::new(&b"a"[..], std::io::sink());
+        let mut bus = Bus::<Pointer, Data>::default();
+        for expected in [b'a', 0] {
+            bus.dispatch_read(DATA);
+            console.cycle(&mut bus);
+            assert!(bus.read_data() == Some(expected));
+        }
+        bus.dispatch_read(STATUS);
+        console.cycle(&mut bus);
+        assert!(bus.read_data() == Some(1));
+    }
+}
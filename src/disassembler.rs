@@ -0,0 +1,170 @@
+use crate::cpu::Data;
+use crate::instructions::Instruction;
+use crate::instructions::OperandKind;
+use crate::memory::Addressable;
+use crate::symbols::SymbolTable;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedLine {
+    pub address: usize,
+    pub label: Option<String>,
+    pub bytes: Vec<Data>,
+    pub text: String,
+}
+
+impl std::fmt::Display for DecodedLine {
+    fn fmt(&self, frmtr: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(label) = &self.label {
+            writeln!(frmtr, "{label}:")?;
+        }
+        let bytes = self.bytes.iter().map(|byte| format!("{byte:02x}")).collect::<Vec<String>>().join(" ");
+        write!(frmtr, "{:#06x}  {bytes:<12}  {}", self.address, self.text)
+    }
+}
+
+#[derive(Debug)]
+pub struct Disassembler<'d, Memory> {
+    memory: &'d Memory,
+    symbols: Option<&'d SymbolTable>,
+}
+
+impl<'d, Memory> Disassembler<'d, Memory>
+where
+    Memory: Addressable<usize, Data = Data>,
+{
+    pub fn build(memory: &'d Memory) -> Self {
+        Self { memory, symbols: None }
+    }
+
+    pub fn with_symbols(mut self, symbols: &'d SymbolTable) -> Self {
+        self.symbols = Some(symbols);
+        self
+    }
+
+    pub fn listing(&self, range: std::ops::Range<usize>) -> Vec<DecodedLine> {
+        let mut lines = Vec::new();
+        let mut address = range.start;
+        while address < range.end {
+            let line = self.decode(address, range.end);
+            address += line.bytes.len();
+            lines.push(line);
+        }
+        lines
+    }
+
+    /// decodes one instruction, falling back to a `.byte` line for anything that is not a whole valid instruction
+    pub fn decode(&self, address: usize, end: usize) -> DecodedLine {
+        let opcode = self.memory.read(address);
+        let label = self.symbols.and_then(|symbols| symbols.name(address)).map(String::from);
+        let data = |bytes: Vec<Data>| {
+            let text = bytes.iter().map(|byte| format!("{byte:#04x}")).collect::<Vec<String>>().join(", ");
+            DecodedLine { address, label: label.clone(), text: format!(".byte {text}"), bytes }
+        };
+
+        let Some(instruction) = Instruction::decode(opcode)
+        else {
+            return data(vec![opcode]);
+        };
+        let kinds = instruction.operand_kinds();
+        if address + kinds.len() >= end {
+            return data((address..end).map(|address| self.memory.read(address)).collect());
+        }
+
+        let operands = (address + 1..=address + kinds.len()).map(|address| self.memory.read(address));
+        let mut texts = Vec::<String>::new();
+        let mut previous = Data::default();
+        for (kind, operand) in kinds.iter().zip(operands.clone()) {
+            match kind {
+                | OperandKind::Wide => {
+                    texts.pop();
+                    let value = u16::from_be_bytes([previous, operand]);
+                    texts.push(match self.symbols.and_then(|symbols| symbols.name(value as usize)) {
+                        | Some(name) => name.to_string(),
+                        | None => format!("{value:#06x}"),
+                    });
+                }
+                | OperandKind::Offset => {
+                    let base = texts.pop().expect("an offset always follows its base");
+                    texts.push(match operand as i8 {
+                        | 0 => format!("[{base}]"),
+                        | offset => format!("[{base}{offset:+}]"),
+                    });
+                }
+                | kind => texts.push(self.operand(*kind, operand)),
+            }
+            previous = operand;
+        }
+        let text = texts.join(", ");
+        let text = match text.is_empty() {
+            | true => instruction.mnemonic().to_string(),
+            | false => format!("{} {text}", instruction.mnemonic()),
+        };
+        DecodedLine { address, label, bytes: std::iter::once(opcode).chain(operands).collect(), text }
+    }
+
+    fn operand(&self, kind: OperandKind, operand: Data) -> String {
+        match kind {
+            | OperandKind::Register => format!("r{operand}"),
+            | OperandKind::Pair => format!("r{operand}:r{}", operand as usize + 1),
+            | OperandKind::Immediate | OperandKind::Offset | OperandKind::Wide => format!("{operand}"),
+            | OperandKind::StackOffset => match operand as i8 {
+                | 0 => "[sp]".to_string(),
+                | offset => format!("[sp{offset:+}]"),
+            },
+            | OperandKind::Address => match self.symbols.and_then(|symbols| symbols.name(operand as usize)) {
+                | Some(name) => name.to_string(),
+                | None => format!("{operand:#04x}"),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assembler::ProgramAssembler;
+    use crate::memory::MemoryBlock;
+
+    use super::*;
+
+    #[test]
+    fn decoded_operands() {
+        let mut mem = MemoryBlock::<u8>::new(16);
+        ProgramAssembler::build(&mut mem).assemble_source("LOADIMM r3, 10\nJUMP 0x0c\nADD r0, r1, r2").unwrap();
+        let texts = Disassembler::build(&mem).listing(0..9).into_iter().map(|line| line.text).collect::<Vec<String>>();
+        assert!(texts == ["LOADIMM r3, 10", "JUMP 0x0c", "ADD r0, r1, r2"]);
+    }
+
+    #[test]
+    fn labels_from_symbols() {
+        let mut mem = MemoryBlock::<u8>::new(16);
+        let mut writer = ProgramAssembler::build(&mut mem);
+        writer.assemble_source("HALT\ntop: PUSH r1\nJUMP top").unwrap();
+        let symbols = writer.symbols().clone();
+
+        let lines = Disassembler::build(&mem).with_symbols(&symbols).listing(0..5);
+        assert!(lines[1].label.as_deref() == Some("top"));
+        assert!(lines[2].text == "JUMP top");
+        assert!(lines[2].to_string() == "0x0003  0a 01         JUMP top");
+    }
+
+    #[test]
+    fn memory_operands() {
+        let mut mem = MemoryBlock::<u8>::new(16);
+        let source = "LOADIND r0, [r1]\nSTOREPAIR [r2:r3-2], r4\nLOADIMMW r4:r5, 0x1234\nLOADSP r0, [sp+1]";
+        ProgramAssembler::build(&mut mem).assemble_source(source).unwrap();
+        let texts = Disassembler::build(&mem).listing(0..15).into_iter().map(|line| line.text).collect::<Vec<String>>();
+        assert!(
+            texts == ["LOADIND r0, [r1]", "STOREPAIR [r2:r3-2], r4", "LOADIMMW r4:r5, 0x1234", "LOADSP r0, [sp+1]"]
+        );
+    }
+
+    #[test]
+    fn invalid_opcodes() {
+        let mut mem = MemoryBlock::<u8>::new(16);
+        ProgramAssembler::build(&mut mem).assemble_source(".byte 0xFF, 0x06, 0, 1").unwrap();
+        let lines = Disassembler::build(&mem).listing(0..4);
+        assert!(lines[0].text == ".byte 0xff");
+        assert!(lines[1].text == ".byte 0x06, 0x00, 0x01");
+        assert!(lines.len() == 2);
+    }
+}
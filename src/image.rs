@@ -0,0 +1,264 @@
+use crate::cpu::Data;
+use crate::cpu::Pointer;
+use crate::cpu::Processor;
+use crate::linker::Program;
+use crate::linker::Segment;
+use crate::memory::Addressable;
+use crate::memory::MemoryBlock;
+use crate::object::Section;
+use crate::symbols::SymbolTable;
+
+const IMAGE_MAGIC: [u8; 4] = *b"PETI";
+const IMAGE_VERSION: u8 = 1;
+const SYMBOLS_FLAG: u8 = 0b1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImageError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    WordSize { data: u8, pointer: u8 },
+    Truncated,
+    InvalidSection(u8),
+    InvalidSymbol,
+    AddressOutOfRange(usize),
+    SymbolName(String),
+    SegmentOutOfBounds { address: usize, end: usize, size: usize },
+    RegisterOutOfBounds { register: &'static str, address: Pointer, size: usize },
+}
+
+impl std::fmt::Display for ImageError {
+    fn fmt(&self, frmtr: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            | Self::BadMagic => write!(frmtr, "not a program image"),
+            | Self::UnsupportedVersion(version) => write!(frmtr, "image version {version} is not supported"),
+            | Self::WordSize { data, pointer } => write!(
+                frmtr,
+                "image targets {data} bit data and {pointer} bit pointers but this machine uses {} and {}",
+                Data::BITS,
+                Pointer::BITS
+            ),
+            | Self::Truncated => write!(frmtr, "image ends early"),
+            | Self::InvalidSection(section) => write!(frmtr, "unknown section {section} in image"),
+            | Self::InvalidSymbol => write!(frmtr, "symbol name in image is not valid text"),
+            | Self::AddressOutOfRange(address) => write!(frmtr, "address {address:#x} does not fit in a pointer"),
+            | Self::SymbolName(name) => write!(frmtr, "symbol name `{name}` is too long for an image"),
+            | Self::SegmentOutOfBounds { address, end, size } => {
+                write!(frmtr, "segment {address:#06x}..{end:#06x} does not fit in {size} bytes of memory")
+            }
+            | Self::RegisterOutOfBounds { register, address, size } => {
+                write!(frmtr, "{register} {address:#06x} is outside {size} bytes of memory")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+/// a linked program ready to be written out or placed into memory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub load_address: Pointer,
+    pub entry: Pointer,
+    pub stack_pointer: Pointer,
+    pub segments: Vec<Segment>,
+    pub symbols: Option<SymbolTable>,
+}
+
+impl Image {
+    /// starts at `main` when the program exports it and at its lowest address otherwise, with the stack at
+    /// `stack_pointer`
+    pub fn from_program(program: &Program, stack_pointer: Pointer) -> Result<Self, ImageError> {
+        let pointer = |address: usize| Pointer::try_from(address).map_err(|_| ImageError::AddressOutOfRange(address));
+        let load_address = pointer(program.segments.iter().map(|segment| segment.address).min().unwrap_or_default())?;
+        pointer(program.end())?;
+        let entry = program.symbols.address("main").map_or(Ok(load_address), pointer)?;
+        Ok(Self { load_address, entry, stack_pointer, segments: program.segments.clone(), symbols: None })
+    }
+
+    pub fn with_entry(mut self, entry: Pointer) -> Self {
+        self.entry = entry;
+        self
+    }
+
+    pub fn with_stack_pointer(mut self, stack_pointer: Pointer) -> Self {
+        self.stack_pointer = stack_pointer;
+        self
+    }
+
+    pub fn with_symbols(mut self, symbols: SymbolTable) -> Self {
+        self.symbols = Some(symbols);
+        self
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ImageError> {
+        let pointer = |address: usize| Pointer::try_from(address).map_err(|_| ImageError::AddressOutOfRange(address));
+        let mut bytes = IMAGE_MAGIC.to_vec();
+        let flags = if self.symbols.is_some() { SYMBOLS_FLAG } else { 0 };
+        bytes.extend([IMAGE_VERSION, Data::BITS as u8, Pointer::BITS as u8, flags]);
+        for word in [self.load_address, self.entry, self.stack_pointer, pointer(self.segments.len())?] {
+            bytes.extend(word.to_le_bytes());
+        }
+        for segment in &self.segments {
+            bytes.push(segment.section as u8);
+            bytes.extend(pointer(segment.address)?.to_le_bytes());
+            bytes.extend(pointer(segment.bytes.len())?.to_le_bytes());
+            bytes.extend(&segment.bytes);
+        }
+
+        if let Some(symbols) = &self.symbols {
+            let symbols = symbols.iter().collect::<Vec<_>>();
+            bytes.extend(pointer(symbols.len())?.to_le_bytes());
+            for (name, address) in symbols {
+                let length = u8::try_from(name.len()).map_err(|_| ImageError::SymbolName(name.into()))?;
+                bytes.push(length);
+                bytes.extend(name.as_bytes());
+                bytes.extend(pointer(address)?.to_le_bytes());
+            }
+        }
+        Ok(bytes)
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, ImageError> {
+        let mut reader = Reader { bytes, head: 0 };
+        if reader.take(IMAGE_MAGIC.len())? != IMAGE_MAGIC {
+            return Err(ImageError::BadMagic);
+        }
+        let version = reader.byte()?;
+        if version != IMAGE_VERSION {
+            return Err(ImageError::UnsupportedVersion(version));
+        }
+        let (data, pointer) = (reader.byte()?, reader.byte()?);
+        if data as u32 != Data::BITS || pointer as u32 != Pointer::BITS {
+            return Err(ImageError::WordSize { data, pointer });
+        }
+        let flags = reader.byte()?;
+        let (load_address, entry, stack_pointer) = (reader.pointer()?, reader.pointer()?, reader.pointer()?);
+
+        let mut segments = Vec::new();
+        for _ in 0..reader.pointer()? {
+            let section = reader.byte()?;
+            let section = *Section::ALL.get(section as usize).ok_or(ImageError::InvalidSection(section))?;
+            let address = reader.pointer()? as usize;
+            let length = reader.pointer()? as usize;
+            segments.push(Segment { section, address, bytes: reader.take(length)?.to_vec() });
+        }
+
+        let mut symbols = None;
+        if flags & SYMBOLS_FLAG != 0 {
+            let mut table = SymbolTable::default();
+            for _ in 0..reader.pointer()? {
+                let length = reader.byte()? as usize;
+                let name = std::str::from_utf8(reader.take(length)?).map_err(|_| ImageError::InvalidSymbol)?;
+                table.insert(name, reader.pointer()? as usize);
+            }
+            symbols = Some(table);
+        }
+        Ok(Self { load_address, entry, stack_pointer, segments, symbols })
+    }
+
+    /// copies every segment into memory and points the processor at the entry and the stack
+    pub fn load(&self, ram: &mut MemoryBlock<Data>, cpu: &mut Processor) -> Result<(), ImageError> {
+        let size = ram.size();
+        for segment in &self.segments {
+            let (address, end) = (segment.address, segment.address + segment.bytes.len());
+            if end > size {
+                return Err(ImageError::SegmentOutOfBounds { address, end, size });
+            }
+        }
+        for (register, address) in [("entry point", self.entry), ("stack pointer", self.stack_pointer)] {
+            if address as usize >= size {
+                return Err(ImageError::RegisterOutOfBounds { register, address, size });
+            }
+        }
+
+        for segment in &self.segments {
+            for (address, byte) in (segment.address..).zip(&segment.bytes) {
+                *ram.write(address) = *byte;
+            }
+        }
+        cpu.program_counter = self.entry;
+        cpu.stack_pointer = self.stack_pointer;
+        cpu.stack_base = self.stack_pointer;
+        cpu.stack_limit = self.stack_limit();
+        Ok(())
+    }
+
+    /// the last byte the image occupies below its stack, so a full stack stops short of the program
+    pub fn stack_limit(&self) -> Pointer {
+        let ends = self.segments.iter().map(|segment| segment.address + segment.bytes.len());
+        let end = ends.filter(|end| *end <= self.stack_pointer as usize).max().unwrap_or_default();
+        end.saturating_sub(1) as Pointer
+    }
+}
+
+struct Reader<'d> {
+    bytes: &'d [u8],
+    head: usize,
+}
+
+impl<'d> Reader<'d> {
+    fn take(&mut self, length: usize) -> Result<&'d [u8], ImageError> {
+        let bytes = self.bytes.get(self.head..self.head + length).ok_or(ImageError::Truncated)?;
+        self.head += length;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, ImageError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn pointer(&mut self) -> Result<Pointer, ImageError> {
+        let bytes = self.take(size_of::<Pointer>())?;
+        Ok(Pointer::from_le_bytes(bytes.try_into().expect("took exactly one pointer")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assembler::assemble_object;
+    use crate::linker::Layout;
+    use crate::linker::Linker;
+
+    use super::*;
+
+    fn image() -> Image {
+        let source = ".global main\n.data\nvalue: .byte 5\n.code\nmain: LOADIMM r0, value\nHALT";
+        let mut linker = Linker::build(Layout { code: 0x20, ..Default::default() });
+        linker.add("app", assemble_object(source, 8).unwrap());
+        let program = linker.link().unwrap();
+        Image::from_program(&program, 0x3F).unwrap().with_symbols(program.symbols)
+    }
+
+    #[test]
+    fn bytes_roundtrip() {
+        let image = image();
+        assert!(image.load_address == 0x20 && image.entry == 0x20);
+        let bytes = image.to_bytes().unwrap();
+        assert!(bytes[..8] == [b'P', b'E', b'T', b'I', 1, 8, 16, 1]);
+        assert!(Image::parse(&bytes).unwrap() == image);
+    }
+
+    #[test]
+    fn rejected_images() {
+        let bytes = image().to_bytes().unwrap();
+        assert!(Image::parse(&bytes[..bytes.len() - 1]) == Err(ImageError::Truncated));
+        assert!(Image::parse(b"ELF\x7f") == Err(ImageError::BadMagic));
+
+        let mut wide = bytes.clone();
+        wide[6] = 32;
+        assert!(Image::parse(&wide) == Err(ImageError::WordSize { data: 8, pointer: 32 }));
+    }
+
+    #[test]
+    fn loaded_registers() {
+        let mut ram = MemoryBlock::<Data>::new(64);
+        let mut cpu = Processor::default();
+        image().load(&mut ram, &mut cpu).unwrap();
+        assert!(cpu.program_counter == 0x20 && cpu.stack_pointer == 0x3F && cpu.stack_limit == 0x24);
+        assert!(ram.read(0x22_usize) == 0x24 && ram.read(0x24_usize) == 5);
+
+        let mut small = MemoryBlock::<Data>::new(32);
+        let error = image().load(&mut small, &mut cpu).unwrap_err();
+        assert!(error == ImageError::SegmentOutOfBounds { address: 0x20, end: 0x24, size: 32 });
+    }
+}
@@ -1,9 +1,11 @@
 use crate::bus::Bus;
 use crate::cpu::Data;
+use crate::cpu::FaultKind;
+use crate::cpu::Frame;
 use crate::cpu::MicroState;
 use crate::cpu::Pointer;
+use crate::cpu::ProcFlags;
 use crate::cpu::Processor;
-use crate::memory::Addressable;
 
 #[repr(u8)]
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
@@ -13,30 +15,79 @@ pub enum Instruction {
     Null,
     Halt,
     Ret,
-    LoadImm,    // dstr, valu
-    LoadMem,    // dstr, addr
-    Copy,       // dstr, reg1
-    Add,        // dstr, reg1, reg2
-    Sub,        // dstr, reg1, reg2
-    Mul,        // dstr, reg1, reg2
-    Div,        // dstr, reg1, reg2
-    Jump,       // addr
-    JumpIfZero, // addr
-    Push,       // reg1
-    Pop,        // reg1
-    Compare,    // reg1, reg2
-    Increment,  // dstr
-    Decrement,  // dstr
+    LoadImm,                // dstr, valu
+    LoadMem,                // dstr, addr
+    Copy,                   // dstr, reg1
+    Add,                    // dstr, reg1, reg2
+    Sub,                    // dstr, reg1, reg2
+    Mul,                    // dstr, reg1, reg2
+    Div,                    // dstr, reg1, reg2
+    Jump,                   // addr
+    JumpIfZero,             // addr, legacy: taken when the zero flag is clear, the same as JumpNotEqual
+    Push,                   // reg1
+    Pop,                    // reg1
+    Compare,                // reg1, reg2
+    Increment,              // dstr
+    Decrement,              // dstr
+    JumpEqual,              // addr
+    JumpNotEqual,           // addr
+    JumpLess,               // addr
+    JumpGreater,            // addr
+    JumpLessEqual,          // addr
+    JumpGreaterEqual,       // addr
+    JumpCarry,              // addr
+    JumpOverflow,           // addr
+    JumpNegative,           // addr
+    JumpLessSigned,         // addr
+    JumpGreaterSigned,      // addr
+    JumpLessEqualSigned,    // addr
+    JumpGreaterEqualSigned, // addr
+    Call,                   // addr
+    CallReg,                // reg1
+    And,                    // dstr, reg1, reg2
+    Or,                     // dstr, reg1, reg2
+    Xor,                    // dstr, reg1, reg2
+    Not,                    // dstr, reg1
+    ShiftLeft,              // dstr, reg1, valu
+    ShiftRight,             // dstr, reg1, valu
+    ShiftRightArithmetic,   // dstr, reg1, valu
+    RotateLeft,             // dstr, through carry
+    RotateRight,            // dstr, through carry
+    BitTest,                // reg1, valu
+    BitSet,                 // dstr, valu
+    BitClear,               // dstr, valu
+    Store,                  // addr, reg1
+    LoadIndirect,           // dstr, [reg1 + offs]
+    StoreIndirect,          // [dstr + offs], reg1
+    LoadPair,               // dstr, [pair + offs]
+    StorePair,              // [pair + offs], reg1
+    LoadImmWide,            // dstp, wide
+    AddWide,                // dstp, pai1, pai2
+    SubWide,                // dstp, pai1, pai2
+    IncrementWide,          // dstp
+    DecrementWide,          // dstp
+    CompareWide,            // pai1, pai2
+    GetStackPointer,        // dstp
+    SetStackPointer,        // pai1
+    GetProgramCounter,      // dstp
+    AdjustStack,            // valu
+    LoadStack,              // dstr, [sp + offs]
+    StoreStack,             // [sp + offs], reg1
+    SetStack,               // pai1 limit, pai2 base
+    EnableInterrupts,
+    DisableInterrupts,
+    ReturnFromInterrupt,
+    Syscall,  // valu
+    JumpWide, // wide
+    CallWide, // wide
     EnumLength,
 }
 
-impl From<u8> for Instruction {
-    fn from(value: u8) -> Self {
-        if value >= Instruction::EnumLength.into() {
-            panic!("this can only be explained by corrupted bytes\ndecoded value: {value}");
-        }
+impl TryFrom<u8> for Instruction {
+    type Error = FaultKind;
 
-        unsafe { std::mem::transmute::<u8, Instruction>(value) }
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Instruction::decode(value).ok_or(FaultKind::InvalidOpcode(value))
     }
 }
 
@@ -46,38 +97,214 @@ impl From<Instruction> for u8 {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperandKind {
+    Register,
+    /// two consecutive registers read as one pointer, high byte in the named one
+    Pair,
+    Immediate,
+    Address,
+    /// signed displacement added to the register or pair right before it
+    Offset,
+    /// low byte of a sixteen bit value whose high byte is the immediate right before it
+    Wide,
+    /// signed displacement from the stack pointer, written `[sp+1]`
+    StackOffset,
+}
+
 impl Instruction {
+    pub fn decode(value: u8) -> Option<Instruction> {
+        if value >= Instruction::EnumLength.into() {
+            return None;
+        }
+
+        Some(unsafe { std::mem::transmute::<u8, Instruction>(value) })
+    }
+
     pub fn operand_count(&self) -> usize {
+        self.operand_kinds().len()
+    }
+
+    pub fn operand_kinds(&self) -> &'static [OperandKind] {
+        use OperandKind::Address;
+        use OperandKind::Immediate;
+        use OperandKind::Offset;
+        use OperandKind::Pair;
+        use OperandKind::Register;
+        use OperandKind::StackOffset;
+        use OperandKind::Wide;
+
         match self {
-            | Instruction::Jump
-            | Instruction::JumpIfZero
+            | Instruction::Jump | Instruction::JumpIfZero | Instruction::Call => &[Address],
+            | Instruction::JumpWide | Instruction::CallWide => &[Immediate, Wide],
+            | instruction if instruction.condition().is_some() => &[Address],
             | Instruction::Push
             | Instruction::Pop
             | Instruction::Increment
-            | Instruction::Decrement => 1,
-            | Instruction::Halt | Instruction::Null | Instruction::Ret => 0,
-            | Instruction::Add | Instruction::Sub | Instruction::Mul | Instruction::Div => 3,
-            | Instruction::LoadImm | Instruction::LoadMem | Instruction::Copy | Instruction::Compare => 2,
+            | Instruction::Decrement
+            | Instruction::CallReg
+            | Instruction::RotateLeft
+            | Instruction::RotateRight => &[Register],
+            | Instruction::Halt
+            | Instruction::Null
+            | Instruction::Ret
+            | Instruction::EnableInterrupts
+            | Instruction::DisableInterrupts
+            | Instruction::ReturnFromInterrupt => &[],
+            | Instruction::Add
+            | Instruction::Sub
+            | Instruction::Mul
+            | Instruction::Div
+            | Instruction::And
+            | Instruction::Or
+            | Instruction::Xor => &[Register, Register, Register],
+            | Instruction::ShiftLeft | Instruction::ShiftRight | Instruction::ShiftRightArithmetic => {
+                &[Register, Register, Immediate]
+            }
+            | Instruction::BitTest | Instruction::BitSet | Instruction::BitClear => &[Register, Immediate],
+            | Instruction::LoadImm => &[Register, Immediate],
+            | Instruction::LoadMem => &[Register, Address],
+            | Instruction::Store => &[Address, Register],
+            | Instruction::LoadIndirect => &[Register, Register, Offset],
+            | Instruction::StoreIndirect => &[Register, Offset, Register],
+            | Instruction::LoadPair => &[Register, Pair, Offset],
+            | Instruction::StorePair => &[Pair, Offset, Register],
+            | Instruction::LoadImmWide => &[Pair, Immediate, Wide],
+            | Instruction::AddWide | Instruction::SubWide => &[Pair, Pair, Pair],
+            | Instruction::IncrementWide | Instruction::DecrementWide => &[Pair],
+            | Instruction::CompareWide | Instruction::SetStack => &[Pair, Pair],
+            | Instruction::GetStackPointer | Instruction::SetStackPointer | Instruction::GetProgramCounter => &[Pair],
+            | Instruction::AdjustStack | Instruction::Syscall => &[Immediate],
+            | Instruction::LoadStack => &[Register, StackOffset],
+            | Instruction::StoreStack => &[StackOffset, Register],
+            | Instruction::Copy | Instruction::Compare | Instruction::Not => &[Register, Register],
             | _ => panic!("this can only be explained by corrupted bytes\ndecoded value: {self:?}"),
         }
     }
+
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            | Instruction::Halt => "HALT",
+            | Instruction::Null => "NULL",
+            | Instruction::Ret => "RET",
+            | Instruction::LoadImm => "LOADIMM",
+            | Instruction::LoadMem => "LOADMEM",
+            | Instruction::Copy => "COPY",
+            | Instruction::Add => "ADD",
+            | Instruction::Sub => "SUB",
+            | Instruction::Mul => "MUL",
+            | Instruction::Div => "DIV",
+            | Instruction::Jump => "JUMP",
+            | Instruction::JumpIfZero => "JUMPIFZERO",
+            | Instruction::Push => "PUSH",
+            | Instruction::Pop => "POP",
+            | Instruction::Compare => "COMPARE",
+            | Instruction::Increment => "INCREMENT",
+            | Instruction::Decrement => "DECREMENT",
+            | Instruction::JumpEqual => "JUMPEQ",
+            | Instruction::JumpNotEqual => "JUMPNE",
+            | Instruction::JumpLess => "JUMPLT",
+            | Instruction::JumpGreater => "JUMPGT",
+            | Instruction::JumpLessEqual => "JUMPLE",
+            | Instruction::JumpGreaterEqual => "JUMPGE",
+            | Instruction::JumpCarry => "JUMPCARRY",
+            | Instruction::JumpOverflow => "JUMPOVERFLOW",
+            | Instruction::JumpNegative => "JUMPNEGATIVE",
+            | Instruction::JumpLessSigned => "JUMPLTS",
+            | Instruction::JumpGreaterSigned => "JUMPGTS",
+            | Instruction::JumpLessEqualSigned => "JUMPLES",
+            | Instruction::JumpGreaterEqualSigned => "JUMPGES",
+            | Instruction::Call => "CALL",
+            | Instruction::CallReg => "CALLREG",
+            | Instruction::And => "AND",
+            | Instruction::Or => "OR",
+            | Instruction::Xor => "XOR",
+            | Instruction::Not => "NOT",
+            | Instruction::ShiftLeft => "SHL",
+            | Instruction::ShiftRight => "SHR",
+            | Instruction::ShiftRightArithmetic => "SAR",
+            | Instruction::RotateLeft => "RCL",
+            | Instruction::RotateRight => "RCR",
+            | Instruction::BitTest => "BTST",
+            | Instruction::BitSet => "BSET",
+            | Instruction::BitClear => "BCLR",
+            | Instruction::Store => "STORE",
+            | Instruction::LoadIndirect => "LOADIND",
+            | Instruction::StoreIndirect => "STOREIND",
+            | Instruction::LoadPair => "LOADPAIR",
+            | Instruction::StorePair => "STOREPAIR",
+            | Instruction::LoadImmWide => "LOADIMMW",
+            | Instruction::AddWide => "ADDW",
+            | Instruction::SubWide => "SUBW",
+            | Instruction::IncrementWide => "INCW",
+            | Instruction::DecrementWide => "DECW",
+            | Instruction::CompareWide => "COMPAREW",
+            | Instruction::GetStackPointer => "GETSP",
+            | Instruction::SetStackPointer => "SETSP",
+            | Instruction::GetProgramCounter => "GETPC",
+            | Instruction::AdjustStack => "ADJSP",
+            | Instruction::LoadStack => "LOADSP",
+            | Instruction::StoreStack => "STORESP",
+            | Instruction::SetStack => "SETSTACK",
+            | Instruction::EnableInterrupts => "EI",
+            | Instruction::DisableInterrupts => "DI",
+            | Instruction::ReturnFromInterrupt => "RETI",
+            | Instruction::Syscall => "SYSCALL",
+            | Instruction::JumpWide => "JUMPW",
+            | Instruction::CallWide => "CALLW",
+            | Instruction::EnumLength => panic!("this can only be explained by corrupted bytes"),
+        }
+    }
+
+    /// the flags a conditional jump tests, read as the outcome of `COMPARE a, b`
+    pub fn condition(&self) -> Option<fn(&ProcFlags) -> bool> {
+        let condition: fn(&ProcFlags) -> bool = match self {
+            | Instruction::JumpEqual => |flags| flags.zero,
+            | Instruction::JumpNotEqual => |flags| !flags.zero,
+            | Instruction::JumpLess => |flags| flags.less,
+            | Instruction::JumpGreater => |flags| flags.great,
+            | Instruction::JumpLessEqual => |flags| flags.less || flags.zero,
+            | Instruction::JumpGreaterEqual => |flags| flags.great || flags.zero,
+            | Instruction::JumpCarry => |flags| flags.carry,
+            | Instruction::JumpOverflow => |flags| flags.overflow,
+            | Instruction::JumpNegative => |flags| flags.negative,
+            | Instruction::JumpLessSigned => |flags| flags.negative != flags.overflow,
+            | Instruction::JumpGreaterSigned => |flags| !flags.zero && flags.negative == flags.overflow,
+            | Instruction::JumpLessEqualSigned => |flags| flags.zero || flags.negative != flags.overflow,
+            | Instruction::JumpGreaterEqualSigned => |flags| flags.negative == flags.overflow,
+            | _ => return None,
+        };
+        Some(condition)
+    }
+
+    pub fn from_mnemonic(name: &str) -> Option<Instruction> {
+        (0..Instruction::EnumLength.into())
+            .filter_map(Instruction::decode)
+            .find(|instruction| instruction.mnemonic().eq_ignore_ascii_case(name))
+    }
 }
 
-pub fn halt<const R: usize>(cpu: &mut Processor<R>) {
+pub fn halt(cpu: &mut Processor) -> Result<(), FaultKind> {
     cpu.halted = true;
     cpu.flags.complete = true;
+    Ok(())
 }
 
-pub fn load_imm<const R: usize>(cpu: &mut Processor<R>) {
+pub fn load_imm(cpu: &mut Processor) -> Result<(), FaultKind> {
     let dst = cpu.operand_buffer.read_next();
     let val = cpu.operand_buffer.read_next();
-    *cpu.registers.write(dst) = val;
+    cpu.set_register(dst, val)?;
     cpu.flags.complete = true;
+    Ok(())
 }
 
-pub fn ret<const R: usize>(cpu: &mut Processor<R>, bus: &mut Bus<Pointer, Data>) {
+/// pops the two byte return address `call` left, low byte first
+pub fn ret(cpu: &mut Processor, bus: &mut Bus<Pointer, Data>) -> Result<(), FaultKind> {
     match cpu.microstate {
         | MicroState(0) => {
+            if cpu.stack_pointer as usize + 2 > cpu.stack_base as usize {
+                return Err(FaultKind::StackUnderflow);
+            }
             assert!(bus.is_avaliable());
             cpu.stack_pointer += 1;
             bus.dispatch_read(cpu.stack_pointer);
@@ -86,107 +313,332 @@ pub fn ret<const R: usize>(cpu: &mut Processor<R>, bus: &mut Bus<Pointer, Data>)
         | MicroState(1) => {
             if let Some(data) = bus.read_data() {
                 cpu.program_counter = data as Pointer;
+                cpu.stack_pointer += 1;
+                bus.dispatch_read(cpu.stack_pointer);
                 cpu.microstate.increment();
             }
         }
-        | _ => cpu.flags.complete = true,
+        | MicroState(2) => {
+            if let Some(data) = bus.read_data() {
+                cpu.program_counter |= (data as Pointer) << 8;
+                cpu.microstate.increment();
+            }
+        }
+        | _ => {
+            cpu.flags.complete = true;
+        }
     }
+    Ok(())
 }
 
-pub fn load_mem<const R: usize>(cpu: &mut Processor<R>, bus: &mut Bus<Pointer, Data>) {
+pub fn enable_interrupts(cpu: &mut Processor) -> Result<(), FaultKind> {
+    cpu.flags.interrupt_enable = true;
+    cpu.flags.complete = true;
+    Ok(())
+}
+
+pub fn disable_interrupts(cpu: &mut Processor) -> Result<(), FaultKind> {
+    cpu.flags.interrupt_enable = false;
+    cpu.flags.complete = true;
+    Ok(())
+}
+
+/// pops the flags an interrupt pushed and then its return address, which turns interrupts back on
+pub fn return_from_interrupt(cpu: &mut Processor, bus: &mut Bus<Pointer, Data>) -> Result<(), FaultKind> {
+    match cpu.microstate {
+        | MicroState(0) => {
+            if cpu.stack_pointer as usize + 3 > cpu.stack_base as usize {
+                return Err(FaultKind::StackUnderflow);
+            }
+            assert!(bus.is_avaliable());
+            cpu.stack_pointer += 1;
+            bus.dispatch_read(cpu.stack_pointer);
+            cpu.microstate.increment();
+        }
+        | MicroState(1) => {
+            if let Some(data) = bus.read_data() {
+                cpu.flags.load_byte(data);
+                cpu.stack_pointer += 1;
+                bus.dispatch_read(cpu.stack_pointer);
+                cpu.microstate.increment();
+            }
+        }
+        | MicroState(2) => {
+            if let Some(data) = bus.read_data() {
+                cpu.program_counter = data as Pointer;
+                cpu.stack_pointer += 1;
+                bus.dispatch_read(cpu.stack_pointer);
+                cpu.microstate.increment();
+            }
+        }
+        | MicroState(3) => {
+            if let Some(data) = bus.read_data() {
+                cpu.program_counter |= (data as Pointer) << 8;
+                cpu.microstate.increment();
+            }
+        }
+        | _ => {
+            cpu.flags.complete = true;
+        }
+    }
+    Ok(())
+}
+
+pub fn syscall(cpu: &mut Processor) -> Result<(), FaultKind> {
+    cpu.syscall_request = Some(cpu.operand_buffer.read_next());
+    cpu.flags.complete = true;
+    Ok(())
+}
+
+pub fn call(cpu: &mut Processor, bus: &mut Bus<Pointer, Data>) -> Result<(), FaultKind> {
+    let adr = cpu.operand_buffer.read_next();
+    call_to(cpu, bus, adr as Pointer)
+}
+
+pub fn call_reg(cpu: &mut Processor, bus: &mut Bus<Pointer, Data>) -> Result<(), FaultKind> {
+    let rg1 = cpu.operand_buffer.read_next();
+    let target = cpu.register(rg1)? as Pointer;
+    call_to(cpu, bus, target)
+}
+
+/// reaches the whole address space, the target follows high byte first
+pub fn call_wide(cpu: &mut Processor, bus: &mut Bus<Pointer, Data>) -> Result<(), FaultKind> {
+    let high = cpu.operand_buffer.read_next();
+    let low = cpu.operand_buffer.read_next();
+    call_to(cpu, bus, Pointer::from_be_bytes([high, low]))
+}
+
+/// pushes the address of the next instruction high byte first, so it reads little endian from `sp + 1`
+fn call_to(cpu: &mut Processor, bus: &mut Bus<Pointer, Data>, target: Pointer) -> Result<(), FaultKind> {
+    let return_address = cpu.program_counter;
+    match cpu.microstate {
+        | MicroState(0) => {
+            if (cpu.stack_pointer as usize) < cpu.stack_limit as usize + 2 {
+                return Err(FaultKind::StackOverflow);
+            }
+            assert!(bus.is_avaliable());
+            bus.dispatch_write(cpu.stack_pointer, (return_address >> 8) as Data);
+            cpu.stack_pointer -= 1;
+            cpu.microstate.increment();
+        }
+        | MicroState(1) => {
+            assert!(bus.is_avaliable());
+            bus.dispatch_write(cpu.stack_pointer, return_address as Data);
+            cpu.stack_pointer -= 1;
+            cpu.microstate.increment();
+        }
+        | _ => {
+            let stack_pointer = cpu.stack_pointer;
+            cpu.call_stack.push(Frame { call_site: cpu.instruction_address, target, return_address, stack_pointer });
+            cpu.program_counter = target;
+            cpu.flags.complete = true;
+        }
+    }
+    Ok(())
+}
+
+pub fn load_mem(cpu: &mut Processor, bus: &mut Bus<Pointer, Data>) -> Result<(), FaultKind> {
     let dst = cpu.operand_buffer.read_next();
     let adr = cpu.operand_buffer.read_next();
+    load_from(cpu, bus, dst, adr as Pointer)
+}
+
+pub fn load_indirect(cpu: &mut Processor, bus: &mut Bus<Pointer, Data>) -> Result<(), FaultKind> {
+    let dst = cpu.operand_buffer.read_next();
+    let rg1 = cpu.operand_buffer.read_next();
+    let off = cpu.operand_buffer.read_next();
+    let address = displace(cpu.register(rg1)? as Pointer, off);
+    load_from(cpu, bus, dst, address)
+}
+
+pub fn load_pair(cpu: &mut Processor, bus: &mut Bus<Pointer, Data>) -> Result<(), FaultKind> {
+    let dst = cpu.operand_buffer.read_next();
+    let pair = cpu.operand_buffer.read_next();
+    let off = cpu.operand_buffer.read_next();
+    let address = displace(cpu.read_pair(pair)?, off);
+    load_from(cpu, bus, dst, address)
+}
+
+pub fn store(cpu: &mut Processor, bus: &mut Bus<Pointer, Data>) -> Result<(), FaultKind> {
+    let adr = cpu.operand_buffer.read_next();
+    let rg1 = cpu.operand_buffer.read_next();
+    store_to(cpu, bus, adr as Pointer, rg1)
+}
+
+pub fn store_indirect(cpu: &mut Processor, bus: &mut Bus<Pointer, Data>) -> Result<(), FaultKind> {
+    let dst = cpu.operand_buffer.read_next();
+    let off = cpu.operand_buffer.read_next();
+    let rg1 = cpu.operand_buffer.read_next();
+    let address = displace(cpu.register(dst)? as Pointer, off);
+    store_to(cpu, bus, address, rg1)
+}
+
+pub fn store_pair(cpu: &mut Processor, bus: &mut Bus<Pointer, Data>) -> Result<(), FaultKind> {
+    let pair = cpu.operand_buffer.read_next();
+    let off = cpu.operand_buffer.read_next();
+    let rg1 = cpu.operand_buffer.read_next();
+    let address = displace(cpu.read_pair(pair)?, off);
+    store_to(cpu, bus, address, rg1)
+}
+
+pub fn load_stack(cpu: &mut Processor, bus: &mut Bus<Pointer, Data>) -> Result<(), FaultKind> {
+    let dst = cpu.operand_buffer.read_next();
+    let off = cpu.operand_buffer.read_next();
+    let address = displace(cpu.stack_pointer, off);
+    load_from(cpu, bus, dst, address)
+}
+
+pub fn store_stack(cpu: &mut Processor, bus: &mut Bus<Pointer, Data>) -> Result<(), FaultKind> {
+    let off = cpu.operand_buffer.read_next();
+    let rg1 = cpu.operand_buffer.read_next();
+    let address = displace(cpu.stack_pointer, off);
+    store_to(cpu, bus, address, rg1)
+}
+
+/// offsets are signed bytes and the sum wraps around the address space
+fn displace(base: Pointer, offset: Data) -> Pointer {
+    base.wrapping_add_signed(offset as i8 as i16)
+}
+
+fn store_to(cpu: &mut Processor, bus: &mut Bus<Pointer, Data>, address: Pointer, src: Data) -> Result<(), FaultKind> {
+    let val = cpu.register(src)?;
+    assert!(bus.is_avaliable());
+    bus.dispatch_write(address, val);
+    cpu.flags.complete = true;
+    Ok(())
+}
+
+fn load_from(cpu: &mut Processor, bus: &mut Bus<Pointer, Data>, dst: Data, address: Pointer) -> Result<(), FaultKind> {
     match cpu.microstate {
         | MicroState(0) => {
             assert!(bus.is_avaliable());
-            bus.dispatch_read(adr as Pointer);
+            bus.dispatch_read(address);
             cpu.microstate.increment();
         }
         | MicroState(1) => {
             if let Some(data) = bus.read_data() {
-                *cpu.registers.write(dst) = data;
+                cpu.set_register(dst, data)?;
                 cpu.microstate.increment();
             }
         }
         | _ => cpu.flags.complete = true,
     }
+    Ok(())
 }
 
-pub fn copy<const R: usize>(cpu: &mut Processor<R>) {
+pub fn copy(cpu: &mut Processor) -> Result<(), FaultKind> {
     let dst = cpu.operand_buffer.read_next();
     let rg1 = cpu.operand_buffer.read_next();
-    let val = cpu.registers.read(rg1);
-    *cpu.registers.write(dst) = val;
+    let val = cpu.register(rg1)?;
+    cpu.set_register(dst, val)?;
     cpu.flags.complete = true;
+    Ok(())
 }
 
-pub fn add<const R: usize>(cpu: &mut Processor<R>) {
+pub fn add(cpu: &mut Processor) -> Result<(), FaultKind> {
     let dst = cpu.operand_buffer.read_next();
     let rg1 = cpu.operand_buffer.read_next();
     let rg2 = cpu.operand_buffer.read_next();
-    let va1 = cpu.registers.read(rg1);
-    let va2 = cpu.registers.read(rg2);
-    *cpu.registers.write(dst) = arithmetic::add(va1, va2);
+    let va1 = cpu.register(rg1)?;
+    let va2 = cpu.register(rg2)?;
+    let outcome = arithmetic::add_carry(va1, va2, false);
+    cpu.set_register(dst, outcome.value)?;
+    cpu.flags.set_arithmetic(outcome);
     cpu.flags.complete = true;
+    Ok(())
 }
 
-pub fn sub<const R: usize>(cpu: &mut Processor<R>) {
+pub fn sub(cpu: &mut Processor) -> Result<(), FaultKind> {
     let dst = cpu.operand_buffer.read_next();
     let rg1 = cpu.operand_buffer.read_next();
     let rg2 = cpu.operand_buffer.read_next();
-    let va1 = cpu.registers.read(rg1);
-    let va2 = cpu.registers.read(rg2);
-    *cpu.registers.write(dst) = arithmetic::sub(va1, va2);
+    let va1 = cpu.register(rg1)?;
+    let va2 = cpu.register(rg2)?;
+    let outcome = arithmetic::sub_borrow(va1, va2, false);
+    cpu.set_register(dst, outcome.value)?;
+    cpu.flags.set_arithmetic(outcome);
     cpu.flags.complete = true;
+    Ok(())
 }
 
-pub fn mul<const R: usize>(cpu: &mut Processor<R>) {
+pub fn mul(cpu: &mut Processor) -> Result<(), FaultKind> {
     let dst = cpu.operand_buffer.read_next();
     let rg1 = cpu.operand_buffer.read_next();
     let rg2 = cpu.operand_buffer.read_next();
-    let va1 = cpu.registers.read(rg1);
-    let va2 = cpu.registers.read(rg2);
-    *cpu.registers.write(dst) = arithmetic::mul(va1, va2);
+    let va1 = cpu.register(rg1)?;
+    let va2 = cpu.register(rg2)?;
+    let outcome = arithmetic::mul_outcome(va1, va2);
+    cpu.set_register(dst, outcome.value)?;
+    cpu.flags.set_arithmetic(outcome);
     cpu.flags.complete = true;
+    Ok(())
 }
 
-pub fn div<const R: usize>(cpu: &mut Processor<R>) {
+pub fn div(cpu: &mut Processor) -> Result<(), FaultKind> {
     let dst = cpu.operand_buffer.read_next();
     let rg1 = cpu.operand_buffer.read_next();
     let rg2 = cpu.operand_buffer.read_next();
-    let va1 = cpu.registers.read(rg1);
-    let va2 = cpu.registers.read(rg2);
-    *cpu.registers.write(dst) = arithmetic::div(va1, va2);
+    let va1 = cpu.register(rg1)?;
+    let va2 = cpu.register(rg2)?;
+    let quotient = arithmetic::div(va1, va2).ok_or(FaultKind::DivideByZero)?;
+    cpu.set_register(dst, quotient)?;
     cpu.flags.complete = true;
+    Ok(())
 }
 
-pub fn jump<const R: usize>(cpu: &mut Processor<R>) {
+pub fn jump(cpu: &mut Processor) -> Result<(), FaultKind> {
     let adr = cpu.operand_buffer.read_next();
     cpu.program_counter = adr as Pointer;
     cpu.flags.complete = true;
+    Ok(())
+}
+
+pub fn jump_wide(cpu: &mut Processor) -> Result<(), FaultKind> {
+    let high = cpu.operand_buffer.read_next();
+    let low = cpu.operand_buffer.read_next();
+    cpu.program_counter = Pointer::from_be_bytes([high, low]);
+    cpu.flags.complete = true;
+    Ok(())
 }
 
-pub fn jump_if_zero<const R: usize>(cpu: &mut Processor<R>) {
+pub fn jump_if_zero(cpu: &mut Processor) -> Result<(), FaultKind> {
     let adr = cpu.operand_buffer.read_next();
     if !cpu.flags.zero {
         cpu.program_counter = adr as Pointer;
     }
     cpu.flags.complete = true;
+    Ok(())
 }
 
-pub fn push<const R: usize>(cpu: &mut Processor<R>, bus: &mut Bus<Pointer, Data>) {
+pub fn jump_when(cpu: &mut Processor, condition: fn(&ProcFlags) -> bool) -> Result<(), FaultKind> {
+    let adr = cpu.operand_buffer.read_next();
+    if condition(&cpu.flags) {
+        cpu.program_counter = adr as Pointer;
+    }
+    cpu.flags.complete = true;
+    Ok(())
+}
+
+pub fn push(cpu: &mut Processor, bus: &mut Bus<Pointer, Data>) -> Result<(), FaultKind> {
     let rg1 = cpu.operand_buffer.read_next();
-    let val = cpu.registers.read(rg1);
+    let val = cpu.register(rg1)?;
+    if cpu.stack_pointer <= cpu.stack_limit {
+        return Err(FaultKind::StackOverflow);
+    }
     assert!(bus.is_avaliable());
     bus.dispatch_write(cpu.stack_pointer, val);
     cpu.stack_pointer -= 1;
     cpu.flags.complete = true;
+    Ok(())
 }
 
-pub fn pop<const R: usize>(cpu: &mut Processor<R>, bus: &mut Bus<Pointer, Data>) {
+pub fn pop(cpu: &mut Processor, bus: &mut Bus<Pointer, Data>) -> Result<(), FaultKind> {
     let dst = cpu.operand_buffer.read_next();
     match cpu.microstate {
         | MicroState(0) => {
+            if cpu.stack_pointer >= cpu.stack_base {
+                return Err(FaultKind::StackUnderflow);
+            }
             assert!(bus.is_avaliable());
             cpu.stack_pointer += 1;
             bus.dispatch_read(cpu.stack_pointer);
@@ -194,43 +646,307 @@ pub fn pop<const R: usize>(cpu: &mut Processor<R>, bus: &mut Bus<Pointer, Data>)
         }
         | MicroState(1) => {
             if let Some(data) = bus.read_data() {
-                *cpu.registers.write(dst) = data;
+                cpu.set_register(dst, data)?;
                 cpu.microstate.increment();
             }
         }
         | _ => cpu.flags.complete = true,
     }
+    Ok(())
 }
 
-pub fn compare<const R: usize>(cpu: &mut Processor<R>) {
+pub fn compare(cpu: &mut Processor) -> Result<(), FaultKind> {
     let rg1 = cpu.operand_buffer.read_next();
     let rg2 = cpu.operand_buffer.read_next();
-    let va1 = cpu.registers.read(rg1);
-    let va2 = cpu.registers.read(rg2);
+    let va1 = cpu.register(rg1)?;
+    let va2 = cpu.register(rg2)?;
     cpu.flags.reset_logical();
     match logic::compare(va1, va2) {
         | logic::Ordering::Equal => cpu.flags.zero = true,
         | logic::Ordering::Less => cpu.flags.less = true,
         | logic::Ordering::Great => cpu.flags.great = true,
     }
+    cpu.flags.set_arithmetic(arithmetic::sub_borrow(va1, va2, false));
+    cpu.flags.complete = true;
+    Ok(())
+}
+
+pub fn load_imm_wide(cpu: &mut Processor) -> Result<(), FaultKind> {
+    let dst = cpu.operand_buffer.read_next();
+    let high = cpu.operand_buffer.read_next();
+    let low = cpu.operand_buffer.read_next();
+    cpu.write_pair(dst, Pointer::from_be_bytes([high, low]))?;
+    cpu.flags.complete = true;
+    Ok(())
+}
+
+pub fn add_wide(cpu: &mut Processor) -> Result<(), FaultKind> {
+    let dst = cpu.operand_buffer.read_next();
+    let pa1 = cpu.operand_buffer.read_next();
+    let pa2 = cpu.operand_buffer.read_next();
+    let (value, outcome) = arithmetic::add_carry_wide(cpu.read_pair(pa1)?, cpu.read_pair(pa2)?, false);
+    cpu.write_pair(dst, value)?;
+    cpu.flags.set_arithmetic(outcome);
+    cpu.flags.complete = true;
+    Ok(())
+}
+
+pub fn sub_wide(cpu: &mut Processor) -> Result<(), FaultKind> {
+    let dst = cpu.operand_buffer.read_next();
+    let pa1 = cpu.operand_buffer.read_next();
+    let pa2 = cpu.operand_buffer.read_next();
+    let (value, outcome) = arithmetic::sub_borrow_wide(cpu.read_pair(pa1)?, cpu.read_pair(pa2)?, false);
+    cpu.write_pair(dst, value)?;
+    cpu.flags.set_arithmetic(outcome);
     cpu.flags.complete = true;
+    Ok(())
 }
 
-pub fn increment<const R: usize>(cpu: &mut Processor<R>) {
+pub fn increment_wide(cpu: &mut Processor) -> Result<(), FaultKind> {
     let dst = cpu.operand_buffer.read_next();
-    let val = cpu.registers.read(dst);
-    *cpu.registers.write(dst) = arithmetic::add(val, 1);
+    let (value, outcome) = arithmetic::add_carry_wide(cpu.read_pair(dst)?, 1, false);
+    cpu.write_pair(dst, value)?;
+    cpu.flags.set_arithmetic(outcome);
     cpu.flags.complete = true;
+    Ok(())
 }
 
-pub fn decrement<const R: usize>(cpu: &mut Processor<R>) {
+pub fn decrement_wide(cpu: &mut Processor) -> Result<(), FaultKind> {
     let dst = cpu.operand_buffer.read_next();
-    let val = cpu.registers.read(dst);
-    *cpu.registers.write(dst) = arithmetic::sub(val, 1);
+    let (value, outcome) = arithmetic::sub_borrow_wide(cpu.read_pair(dst)?, 1, false);
+    cpu.write_pair(dst, value)?;
+    cpu.flags.set_arithmetic(outcome);
     cpu.flags.complete = true;
+    Ok(())
+}
+
+/// orders the high halves first and falls back to the low ones when they are equal
+pub fn compare_wide(cpu: &mut Processor) -> Result<(), FaultKind> {
+    let pa1 = cpu.operand_buffer.read_next();
+    let pa2 = cpu.operand_buffer.read_next();
+    let [high1, low1] = cpu.read_pair(pa1)?.to_be_bytes();
+    let [high2, low2] = cpu.read_pair(pa2)?.to_be_bytes();
+    cpu.flags.reset_logical();
+    let ordering = match logic::compare(high1, high2) {
+        | logic::Ordering::Equal => logic::compare(low1, low2),
+        | ordering => ordering,
+    };
+    match ordering {
+        | logic::Ordering::Equal => cpu.flags.zero = true,
+        | logic::Ordering::Less => cpu.flags.less = true,
+        | logic::Ordering::Great => cpu.flags.great = true,
+    }
+    let (_, outcome) = arithmetic::sub_borrow_wide(cpu.read_pair(pa1)?, cpu.read_pair(pa2)?, false);
+    cpu.flags.set_arithmetic(outcome);
+    cpu.flags.complete = true;
+    Ok(())
+}
+
+pub fn get_stack_pointer(cpu: &mut Processor) -> Result<(), FaultKind> {
+    let dst = cpu.operand_buffer.read_next();
+    cpu.write_pair(dst, cpu.stack_pointer)?;
+    cpu.flags.complete = true;
+    Ok(())
+}
+
+pub fn set_stack_pointer(cpu: &mut Processor) -> Result<(), FaultKind> {
+    let pa1 = cpu.operand_buffer.read_next();
+    move_stack_pointer(cpu, cpu.read_pair(pa1)? as isize)
+}
+
+/// the address of the next instruction
+pub fn get_program_counter(cpu: &mut Processor) -> Result<(), FaultKind> {
+    let dst = cpu.operand_buffer.read_next();
+    cpu.write_pair(dst, cpu.program_counter)?;
+    cpu.flags.complete = true;
+    Ok(())
+}
+
+/// a negative amount allocates stack space and a positive one frees it
+pub fn adjust_stack(cpu: &mut Processor) -> Result<(), FaultKind> {
+    let amt = cpu.operand_buffer.read_next();
+    let target = cpu.stack_pointer as isize + amt as i8 as isize;
+    move_stack_pointer(cpu, target)
+}
+
+/// gives the stack new bounds and empties it
+pub fn set_stack(cpu: &mut Processor) -> Result<(), FaultKind> {
+    let limit = cpu.operand_buffer.read_next();
+    let base = cpu.operand_buffer.read_next();
+    cpu.stack_limit = cpu.read_pair(limit)?;
+    cpu.stack_base = cpu.read_pair(base)?;
+    cpu.stack_pointer = cpu.stack_base;
+    cpu.call_stack.clear();
+    cpu.flags.complete = true;
+    Ok(())
+}
+
+/// the stack pointer has to stay between the limit and the base
+fn move_stack_pointer(cpu: &mut Processor, target: isize) -> Result<(), FaultKind> {
+    if target < cpu.stack_limit as isize {
+        return Err(FaultKind::StackOverflow);
+    }
+    if target > cpu.stack_base as isize {
+        return Err(FaultKind::StackUnderflow);
+    }
+    cpu.stack_pointer = target as Pointer;
+    cpu.flags.complete = true;
+    Ok(())
+}
+
+pub fn increment(cpu: &mut Processor) -> Result<(), FaultKind> {
+    let dst = cpu.operand_buffer.read_next();
+    let val = cpu.register(dst)?;
+    let outcome = arithmetic::add_carry(val, 1, false);
+    cpu.set_register(dst, outcome.value)?;
+    cpu.flags.set_arithmetic(outcome);
+    cpu.flags.complete = true;
+    Ok(())
+}
+
+pub fn decrement(cpu: &mut Processor) -> Result<(), FaultKind> {
+    let dst = cpu.operand_buffer.read_next();
+    let val = cpu.register(dst)?;
+    let outcome = arithmetic::sub_borrow(val, 1, false);
+    cpu.set_register(dst, outcome.value)?;
+    cpu.flags.set_arithmetic(outcome);
+    cpu.flags.complete = true;
+    Ok(())
+}
+
+pub fn and(cpu: &mut Processor) -> Result<(), FaultKind> {
+    let dst = cpu.operand_buffer.read_next();
+    let rg1 = cpu.operand_buffer.read_next();
+    let rg2 = cpu.operand_buffer.read_next();
+    let va1 = cpu.register(rg1)?;
+    let va2 = cpu.register(rg2)?;
+    let outcome = logic::bitwise(va1 & va2);
+    cpu.set_register(dst, outcome.value)?;
+    cpu.flags.set_arithmetic(outcome);
+    cpu.flags.complete = true;
+    Ok(())
+}
+
+pub fn or(cpu: &mut Processor) -> Result<(), FaultKind> {
+    let dst = cpu.operand_buffer.read_next();
+    let rg1 = cpu.operand_buffer.read_next();
+    let rg2 = cpu.operand_buffer.read_next();
+    let va1 = cpu.register(rg1)?;
+    let va2 = cpu.register(rg2)?;
+    let outcome = logic::bitwise(va1 | va2);
+    cpu.set_register(dst, outcome.value)?;
+    cpu.flags.set_arithmetic(outcome);
+    cpu.flags.complete = true;
+    Ok(())
+}
+
+pub fn xor(cpu: &mut Processor) -> Result<(), FaultKind> {
+    let dst = cpu.operand_buffer.read_next();
+    let rg1 = cpu.operand_buffer.read_next();
+    let rg2 = cpu.operand_buffer.read_next();
+    let va1 = cpu.register(rg1)?;
+    let va2 = cpu.register(rg2)?;
+    let outcome = logic::bitwise(va1 ^ va2);
+    cpu.set_register(dst, outcome.value)?;
+    cpu.flags.set_arithmetic(outcome);
+    cpu.flags.complete = true;
+    Ok(())
+}
+
+pub fn not(cpu: &mut Processor) -> Result<(), FaultKind> {
+    let dst = cpu.operand_buffer.read_next();
+    let rg1 = cpu.operand_buffer.read_next();
+    let val = cpu.register(rg1)?;
+    let outcome = logic::bitwise(!val);
+    cpu.set_register(dst, outcome.value)?;
+    cpu.flags.set_arithmetic(outcome);
+    cpu.flags.complete = true;
+    Ok(())
+}
+
+pub fn shift_left(cpu: &mut Processor) -> Result<(), FaultKind> {
+    let dst = cpu.operand_buffer.read_next();
+    let rg1 = cpu.operand_buffer.read_next();
+    let amt = cpu.operand_buffer.read_next();
+    let outcome = logic::shift_left(cpu.register(rg1)?, amt);
+    cpu.set_register(dst, outcome.value)?;
+    cpu.flags.set_arithmetic(outcome);
+    cpu.flags.complete = true;
+    Ok(())
+}
+
+pub fn shift_right(cpu: &mut Processor) -> Result<(), FaultKind> {
+    let dst = cpu.operand_buffer.read_next();
+    let rg1 = cpu.operand_buffer.read_next();
+    let amt = cpu.operand_buffer.read_next();
+    let outcome = logic::shift_right(cpu.register(rg1)?, amt);
+    cpu.set_register(dst, outcome.value)?;
+    cpu.flags.set_arithmetic(outcome);
+    cpu.flags.complete = true;
+    Ok(())
+}
+
+pub fn shift_right_arithmetic(cpu: &mut Processor) -> Result<(), FaultKind> {
+    let dst = cpu.operand_buffer.read_next();
+    let rg1 = cpu.operand_buffer.read_next();
+    let amt = cpu.operand_buffer.read_next();
+    let outcome = logic::shift_right_arithmetic(cpu.register(rg1)?, amt);
+    cpu.set_register(dst, outcome.value)?;
+    cpu.flags.set_arithmetic(outcome);
+    cpu.flags.complete = true;
+    Ok(())
+}
+
+pub fn rotate_left(cpu: &mut Processor) -> Result<(), FaultKind> {
+    let dst = cpu.operand_buffer.read_next();
+    let outcome = logic::rotate_left(cpu.register(dst)?, cpu.flags.carry);
+    cpu.set_register(dst, outcome.value)?;
+    cpu.flags.set_arithmetic(outcome);
+    cpu.flags.complete = true;
+    Ok(())
+}
+
+pub fn rotate_right(cpu: &mut Processor) -> Result<(), FaultKind> {
+    let dst = cpu.operand_buffer.read_next();
+    let outcome = logic::rotate_right(cpu.register(dst)?, cpu.flags.carry);
+    cpu.set_register(dst, outcome.value)?;
+    cpu.flags.set_arithmetic(outcome);
+    cpu.flags.complete = true;
+    Ok(())
+}
+
+/// sets the zero flag when the bit is clear and leaves every other flag alone
+pub fn bit_test(cpu: &mut Processor) -> Result<(), FaultKind> {
+    let rg1 = cpu.operand_buffer.read_next();
+    let bit = cpu.operand_buffer.read_next();
+    cpu.flags.zero = cpu.register(rg1)? & logic::bit_mask(bit) == 0;
+    cpu.flags.complete = true;
+    Ok(())
+}
+
+pub fn bit_set(cpu: &mut Processor) -> Result<(), FaultKind> {
+    let dst = cpu.operand_buffer.read_next();
+    let bit = cpu.operand_buffer.read_next();
+    let outcome = logic::bitwise(cpu.register(dst)? | logic::bit_mask(bit));
+    cpu.set_register(dst, outcome.value)?;
+    cpu.flags.set_arithmetic(outcome);
+    cpu.flags.complete = true;
+    Ok(())
+}
+
+pub fn bit_clear(cpu: &mut Processor) -> Result<(), FaultKind> {
+    let dst = cpu.operand_buffer.read_next();
+    let bit = cpu.operand_buffer.read_next();
+    let outcome = logic::bitwise(cpu.register(dst)? & !logic::bit_mask(bit));
+    cpu.set_register(dst, outcome.value)?;
+    cpu.flags.set_arithmetic(outcome);
+    cpu.flags.complete = true;
+    Ok(())
 }
 
 pub mod logic {
+    use super::arithmetic::Outcome;
+
     #[derive(PartialEq, Eq, PartialOrd, Ord)]
     pub enum Ordering {
         Equal,
@@ -240,20 +956,66 @@ pub mod logic {
 
     pub fn compare(x: u8, y: u8) -> Ordering {
         let diff = x ^ y;
-        let mut out = Ordering::Equal;
         let mut mask = 1 << 7;
         while mask > 0 {
             if diff & mask != 0 {
-                if x & mask != 0 {
-                    out = Ordering::Great;
-                }
-                if y & mask != 0 {
-                    out = Ordering::Less;
-                }
+                return match x & mask != 0 {
+                    | true => Ordering::Great,
+                    | false => Ordering::Less,
+                };
             }
             mask >>= 1;
         }
-        out
+        Ordering::Equal
+    }
+
+    /// bitwise results never carry or overflow
+    pub fn bitwise(value: u8) -> Outcome {
+        Outcome { value, carry: false, overflow: false }
+    }
+
+    /// bit indices wrap at eight
+    pub fn bit_mask(bit: u8) -> u8 {
+        1 << (bit & 7)
+    }
+
+    /// one bit at a time, the carry is the last bit pushed out
+    pub fn shift_left(mut x: u8, amount: u8) -> Outcome {
+        let mut carry = false;
+        for _ in 0..amount {
+            carry = x & 0x80 != 0;
+            x <<= 1;
+        }
+        Outcome { carry, ..bitwise(x) }
+    }
+
+    pub fn shift_right(mut x: u8, amount: u8) -> Outcome {
+        let mut carry = false;
+        for _ in 0..amount {
+            carry = x & 1 != 0;
+            x >>= 1;
+        }
+        Outcome { carry, ..bitwise(x) }
+    }
+
+    /// like `shift_right` but copies the sign bit into the vacated top bit
+    pub fn shift_right_arithmetic(mut x: u8, amount: u8) -> Outcome {
+        let sign = x & 0x80;
+        let mut carry = false;
+        for _ in 0..amount {
+            carry = x & 1 != 0;
+            x = (x >> 1) | sign;
+        }
+        Outcome { carry, ..bitwise(x) }
+    }
+
+    /// nine bit rotation, the carry goes into bit 0 and bit 7 comes out as the new carry
+    pub fn rotate_left(x: u8, carry: bool) -> Outcome {
+        Outcome { carry: x & 0x80 != 0, ..bitwise((x << 1) | carry as u8) }
+    }
+
+    pub fn rotate_right(x: u8, carry: bool) -> Outcome {
+        Outcome { carry: x & 1 != 0, ..bitwise((x >> 1) | (carry as u8) << 7) }
     }
 
     #[cfg(test)]
@@ -268,6 +1030,27 @@ pub mod logic {
             assert!(compare(x, y) == Ordering::Less);
             assert!(compare(y, x) == Ordering::Great);
             assert!(compare(x, z) == Ordering::Equal);
+            assert!(compare(3, 5) == Ordering::Less);
+            assert!(compare(0x81, 0x7F) == Ordering::Great);
+        }
+
+        #[test]
+        fn shifts() {
+            assert!(shift_left(0b1100_0001, 2) == Outcome { value: 0b0000_0100, carry: true, overflow: false });
+            assert!(shift_right(0b1000_0010, 2) == Outcome { value: 0b0010_0000, carry: true, overflow: false });
+            assert!(shift_right_arithmetic(0b1000_0010, 2).value == 0b1110_0000);
+            assert!(shift_left(0xFF, 9) == bitwise(0) && !shift_right(0x12, 0).carry);
+        }
+
+        #[test]
+        fn rotates_through_carry() {
+            assert!(rotate_left(0b1000_0001, false) == Outcome { value: 0b0000_0010, carry: true, overflow: false });
+            assert!(rotate_right(0b0000_0010, true) == Outcome { value: 0b1000_0001, carry: false, overflow: false });
+            let mut outcome = bitwise(0x5A);
+            for _ in 0..9 {
+                outcome = rotate_left(outcome.value, outcome.carry);
+            }
+            assert!(outcome == bitwise(0x5A));
         }
     }
 }
@@ -299,11 +1082,89 @@ pub mod arithmetic {
         out
     }
 
-    pub fn div(mut x: u8, mut y: u8) -> u8 {
-        assert!(y != 0);
+    /// a result together with the flags it leaves behind
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Outcome {
+        pub value: u8,
+        /// carry out of bit 7, or a borrow into it when subtracting
+        pub carry: bool,
+        /// the signed result does not fit
+        pub overflow: bool,
+    }
+
+    impl Outcome {
+        pub fn negative(&self) -> bool {
+            self.value & 0x80 != 0
+        }
+    }
+
+    /// full adder rippled over every bit, signed overflow is the carry into bit 7 differing from the one out
+    pub fn add_carry(x: u8, y: u8, carry_in: bool) -> Outcome {
+        let mut value = 0;
+        let mut carry = carry_in as u8;
+        let mut into_sign = 0;
+        for bit in 0..8 {
+            let a = (x >> bit) & 1;
+            let b = (y >> bit) & 1;
+            value |= (a ^ b ^ carry) << bit;
+            if bit == 7 {
+                into_sign = carry;
+            }
+            carry = (a & b) | (carry & (a ^ b));
+        }
+        Outcome { value, carry: carry != 0, overflow: into_sign != carry }
+    }
+
+    /// `x - y - borrow_in` as `x + !y + 1`, the carry is reported as a borrow
+    pub fn sub_borrow(x: u8, y: u8, borrow_in: bool) -> Outcome {
+        let outcome = add_carry(x, !y, !borrow_in);
+        Outcome { carry: !outcome.carry, ..outcome }
+    }
+
+    /// sixteen bit mode, two eight bit additions chained through the carry with the flags of the high half
+    pub fn add_carry_wide(x: u16, y: u16, carry_in: bool) -> (u16, Outcome) {
+        let [x_high, x_low] = x.to_be_bytes();
+        let [y_high, y_low] = y.to_be_bytes();
+        let low = add_carry(x_low, y_low, carry_in);
+        let high = add_carry(x_high, y_high, low.carry);
+        (u16::from_be_bytes([high.value, low.value]), high)
+    }
+
+    pub fn sub_borrow_wide(x: u16, y: u16, borrow_in: bool) -> (u16, Outcome) {
+        let [x_high, x_low] = x.to_be_bytes();
+        let [y_high, y_low] = y.to_be_bytes();
+        let low = sub_borrow(x_low, y_low, borrow_in);
+        let high = sub_borrow(x_high, y_high, low.carry);
+        (u16::from_be_bytes([high.value, low.value]), high)
+    }
+
+    /// shift and add, carry and overflow both mean the full product needs more than eight bits
+    pub fn mul_outcome(mut x: u8, mut y: u8) -> Outcome {
+        let mut value = 0;
+        let mut lost = false;
+        while y != 0 {
+            if y & 1 != 0 {
+                let sum = add_carry(value, x, false);
+                value = sum.value;
+                lost |= sum.carry;
+            }
+            y >>= 1;
+            if y != 0 {
+                lost |= x & 0x80 != 0;
+                x <<= 1;
+            }
+        }
+        Outcome { value, carry: lost, overflow: lost }
+    }
+
+    /// restoring division, `None` when dividing by zero
+    pub fn div(mut x: u8, mut y: u8) -> Option<u8> {
+        if y == 0 {
+            return None;
+        }
         let mut q = 0;
         let mut mask = 1;
-        while y <= x {
+        while y <= x && y & 0x80 == 0 {
             y <<= 1;
             mask <<= 1;
         }
@@ -315,7 +1176,7 @@ pub mod arithmetic {
             y >>= 1;
             mask >>= 1;
         }
-        q
+        Some(q)
     }
 
     #[cfg(test)]
@@ -329,6 +1190,18 @@ pub mod arithmetic {
             assert!(add(x, y) == x + y);
         }
 
+        #[test]
+        fn wide_chains() {
+            let (value, outcome) = add_carry_wide(0x12FF, 0x0001, false);
+            assert!(value == 0x1300 && !outcome.carry && !outcome.overflow);
+            let (value, outcome) = add_carry_wide(0x7FFF, 0x0001, false);
+            assert!(value == 0x8000 && outcome.overflow && outcome.negative());
+            let (value, outcome) = sub_borrow_wide(0x0100, 0x0001, false);
+            assert!(value == 0x00FF && !outcome.carry);
+            let (value, outcome) = sub_borrow_wide(0x0000, 0x0001, false);
+            assert!(value == 0xFFFF && outcome.carry && outcome.negative());
+        }
+
         #[test]
         fn ripple_subtractor() {
             let x = 33;
@@ -347,15 +1220,39 @@ pub mod arithmetic {
         fn bit_div() {
             let x = 37;
             let y = 12;
-            assert!(div(x, y) == x / y);
+            assert!(div(x, y) == Some(x / y));
+            assert!(div(200, 1) == Some(200) && div(255, 128) == Some(1) && div(3, 200) == Some(0));
+        }
+
+        #[test]
+        fn carry_flags() {
+            assert!(add_carry(200, 100, false) == Outcome { value: 44, carry: true, overflow: false });
+            assert!(add_carry(100, 27, true) == Outcome { value: 128, carry: false, overflow: true });
+            assert!(add_carry(0x80, 0x80, false) == Outcome { value: 0, carry: true, overflow: true });
+            assert!(add_carry(100, 27, true).negative());
         }
 
         #[test]
-        #[should_panic]
-        fn div_panic() {
+        fn borrow_flags() {
+            assert!(sub_borrow(5, 7, false) == Outcome { value: 254, carry: true, overflow: false });
+            assert!(sub_borrow(7, 5, true) == Outcome { value: 1, carry: false, overflow: false });
+            assert!(sub_borrow(0x80, 1, false) == Outcome { value: 0x7F, carry: false, overflow: true });
+        }
+
+        #[test]
+        fn mul_overflow() {
+            for (x, y) in [(15, 17), (16, 16), (128, 2), (0, 255), (255, 1)] {
+                let outcome = mul_outcome(x, y);
+                assert!(outcome.value == x.wrapping_mul(y));
+                assert!(outcome.carry == (x as u16 * y as u16 > 255));
+            }
+        }
+
+        #[test]
+        fn div_by_zero() {
             let x = 10;
             let y = 0;
-            assert!(div(x, y) == u8::MAX);
+            assert!(div(x, y).is_none());
         }
     }
 }
@@ -379,19 +1276,36 @@ mod tests {
 
     #[test]
     fn enum_transmute() {
-        assert!(Into::<Instruction>::into(0_u8) == Instruction::Halt);
-        assert!(Into::<Instruction>::into(1_u8) == Instruction::Null);
+        assert!(Instruction::try_from(0_u8) == Ok(Instruction::Null));
+        assert!(Instruction::try_from(1_u8) == Ok(Instruction::Halt));
     }
 
     #[test]
     fn u8_transmute() {
-        assert!(Into::<u8>::into(Instruction::Halt) == 0);
-        assert!(Into::<u8>::into(Instruction::Null) == 1);
+        assert!(Into::<u8>::into(Instruction::Null) == 0);
+        assert!(Into::<u8>::into(Instruction::Halt) == 1);
+    }
+
+    #[test]
+    fn mnemonic_roundtrip() {
+        for opcode in 0..Instruction::EnumLength.into() {
+            let instruction = Instruction::try_from(opcode).unwrap();
+            assert!(Instruction::from_mnemonic(instruction.mnemonic()) == Some(instruction));
+        }
+        assert!(Instruction::from_mnemonic("loadimm") == Some(Instruction::LoadImm));
+        assert!(Instruction::from_mnemonic("LOAD").is_none());
+    }
+
+    #[test]
+    fn checked_decode() {
+        assert!(Instruction::decode(Instruction::Ret.into()) == Some(Instruction::Ret));
+        assert!(Instruction::decode(Instruction::EnumLength.into()).is_none());
+        assert!(Instruction::decode(u8::MAX).is_none());
     }
 
     #[test]
-    #[should_panic]
     fn bad_transmute() {
-        let _ = Into::<Instruction>::into(Instruction::EnumLength as u8);
+        let opcode = Instruction::EnumLength as u8;
+        assert!(Instruction::try_from(opcode) == Err(FaultKind::InvalidOpcode(opcode)));
     }
 }
@@ -0,0 +1,143 @@
+use crate::bus::Bus;
+use crate::bus::BusState;
+use crate::bus::Cycle;
+use crate::cpu::Data;
+use crate::cpu::Pointer;
+
+/// lines the controller arbitrates, line 0 has the highest priority
+pub const LINES: usize = Data::BITS as usize;
+
+/// name of the config device the controller's registers are mapped at
+pub const DEVICE_NAME: &str = "interrupts";
+
+/// register offsets inside the controller's region of the memory map
+pub const PENDING: Pointer = 0;
+pub const MASK: Pointer = 1;
+pub const TRIGGER: Pointer = 2;
+
+/// a line number past the controller's `LINES`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidLine(pub usize);
+
+impl std::fmt::Display for InvalidLine {
+    fn fmt(&self, frmtr: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(frmtr, "no interrupt line {}, the controller has {LINES}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidLine {}
+
+/// latches requests from devices and hands the processor the most urgent one it has not masked
+#[derive(Debug)]
+pub struct InterruptController {
+    /// one bit per line, set until the processor takes the interrupt
+    pending: Data,
+    /// lines allowed through to the processor, all of them at power on
+    mask: Data,
+}
+
+impl Default for InterruptController {
+    fn default() -> Self {
+        Self { pending: 0, mask: Data::MAX }
+    }
+}
+
+impl InterruptController {
+    pub fn raise(&mut self, line: usize) -> Result<(), InvalidLine> {
+        if line >= LINES {
+            return Err(InvalidLine(line));
+        }
+        self.pending |= 1 << line;
+        Ok(())
+    }
+
+    pub fn pending(&self) -> Data {
+        self.pending
+    }
+
+    pub fn mask(&self) -> Data {
+        self.mask
+    }
+
+    /// the lowest numbered line that is raised and unmasked
+    pub fn next_line(&self) -> Option<Data> {
+        let active = self.pending & self.mask;
+        (active != 0).then(|| active.trailing_zeros() as Data)
+    }
+
+    pub fn acknowledge(&mut self, line: Data) {
+        self.pending &= !(1 << line);
+    }
+
+    fn read(&self, offset: Pointer) -> Data {
+        match offset {
+            | PENDING => self.pending,
+            | MASK => self.mask,
+            | _ => Default::default(),
+        }
+    }
+
+    /// writing ones to pending clears those lines, writing them to trigger raises them
+    fn write(&mut self, offset: Pointer, data: Data) {
+        match offset {
+            | PENDING => self.pending &= !data,
+            | MASK => self.mask = data,
+            | TRIGGER => self.pending |= data,
+            | _ => {}
+        }
+    }
+}
+
+impl Cycle<Pointer, Data> for InterruptController {
+    fn cycle(&mut self, bus: &mut Bus<Pointer, Data>) {
+        match bus.get_instruction() {
+            | BusState::Read => {
+                let bus_state = bus.complete_dispatch();
+                if let Some(offset) = bus_state.address.take() {
+                    *bus_state.data = Some(self.read(offset));
+                }
+            }
+            | BusState::Write => {
+                let bus_state = bus.complete_dispatch();
+                if let (Some(offset), Some(data)) = (bus_state.address.take(), bus_state.data.take()) {
+                    self.write(offset, data);
+                }
+            }
+            | BusState::Null => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn priorities_and_mask() {
+        let mut controller = InterruptController::default();
+        assert!(controller.next_line().is_none());
+        controller.raise(5).unwrap();
+        controller.raise(2).unwrap();
+        assert!(controller.raise(LINES) == Err(InvalidLine(LINES)) && controller.pending() == 0b10_0100);
+        assert!(controller.next_line() == Some(2));
+        controller.acknowledge(2);
+        assert!(controller.next_line() == Some(5));
+        controller.write(MASK, !(1 << 5));
+        assert!(controller.next_line().is_none() && controller.pending() == 1 << 5);
+    }
+
+    #[test]
+    fn mapped_registers() {
+        let mut controller = InterruptController::default();
+        let mut bus = Bus::<Pointer, Data>::default();
+        bus.dispatch_write(TRIGGER, 0b1001);
+        controller.cycle(&mut bus);
+        assert!(bus.is_avaliable() && controller.pending() == 0b1001);
+
+        bus.dispatch_write(PENDING, 0b0001);
+        controller.cycle(&mut bus);
+        bus.dispatch_read(PENDING);
+        controller.cycle(&mut bus);
+        assert!(bus.read_data() == Some(0b1000));
+    }
+}
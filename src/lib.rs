@@ -0,0 +1,20 @@
+pub mod assembler;
+pub mod bus;
+pub mod clock;
+pub mod config;
+pub mod cpu;
+pub mod decoder;
+pub mod devices;
+pub mod disassembler;
+pub mod image;
+pub mod instructions;
+pub mod interrupts;
+pub mod linker;
+pub mod machine;
+pub mod memory;
+pub mod number;
+pub mod object;
+pub mod symbols;
+pub mod syscalls;
+#[cfg(test)]
+mod testing;
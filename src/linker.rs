@@ -0,0 +1,247 @@
+use crate::cpu::Data;
+use crate::memory::Addressable;
+use crate::object::Object;
+use crate::object::Section;
+use crate::object::Target;
+use crate::symbols::SymbolTable;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkError {
+    DuplicateSymbol { name: String, first: String, second: String },
+    UnresolvedSymbol { name: String, module: String },
+    AddressOutOfRange { module: String, address: i64 },
+    SectionOverlap { first: Section, second: Section },
+}
+
+impl std::fmt::Display for LinkError {
+    fn fmt(&self, frmtr: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            | Self::DuplicateSymbol { name, first, second } => {
+                write!(frmtr, "symbol `{name}` is exported by both `{first}` and `{second}`")
+            }
+            | Self::UnresolvedSymbol { name, module } => {
+                write!(frmtr, "`{module}` uses `{name}` but no module exports it")
+            }
+            | Self::AddressOutOfRange { module, address } => {
+                write!(frmtr, "`{module}` refers to address {address:#06x} which does not fit in its operand")
+            }
+            | Self::SectionOverlap { first, second } => {
+                write!(frmtr, "sections `{}` and `{}` overlap in the layout", first.name(), second.name())
+            }
+        }
+    }
+}
+
+impl std::error::Error for LinkError {}
+
+/// start address of each output section, a section without one follows the section before it
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    pub code: usize,
+    pub data: Option<usize>,
+    pub bss: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub section: Section,
+    pub address: usize,
+    pub bytes: Vec<Data>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Program {
+    pub segments: Vec<Segment>,
+    pub symbols: SymbolTable,
+}
+
+impl Program {
+    pub fn end(&self) -> usize {
+        self.segments.iter().map(|segment| segment.address + segment.bytes.len()).max().unwrap_or_default()
+    }
+
+    pub fn load<Memory>(&self, memory: &mut Memory)
+    where
+        Memory: Addressable<usize, Data = Data>,
+    {
+        for segment in &self.segments {
+            for (address, byte) in (segment.address..).zip(&segment.bytes) {
+                *memory.write(address) = *byte;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Linker {
+    layout: Layout,
+    modules: Vec<(String, Object)>,
+}
+
+impl Linker {
+    pub fn build(layout: Layout) -> Self {
+        Self { layout, modules: Vec::new() }
+    }
+
+    pub fn add(&mut self, name: impl Into<String>, object: Object) -> &mut Self {
+        self.modules.push((name.into(), object));
+        self
+    }
+
+    /// concatenates each section across modules in the order they were added, reporting every problem found
+    pub fn link(&self) -> Result<Program, Vec<LinkError>> {
+        let mut errors = Vec::new();
+        let mut starts = vec![[0; 3]; self.modules.len()];
+        let mut ranges = Vec::new();
+        let mut cursor = self.layout.code;
+        for section in Section::ALL {
+            cursor = match section {
+                | Section::Code => self.layout.code,
+                | Section::Data => self.layout.data.unwrap_or(cursor),
+                | Section::Bss => self.layout.bss.unwrap_or(cursor),
+            };
+            let start = cursor;
+            for (module, (_, object)) in self.modules.iter().enumerate() {
+                starts[module][section as usize] = cursor;
+                cursor += object.section(section).len();
+            }
+            ranges.push((section, start..cursor));
+        }
+        for (index, (first, lhs)) in ranges.iter().enumerate() {
+            for (second, rhs) in &ranges[index + 1..] {
+                if lhs.start < rhs.end && rhs.start < lhs.end {
+                    errors.push(LinkError::SectionOverlap { first: *first, second: *second });
+                }
+            }
+        }
+
+        let mut symbols = SymbolTable::default();
+        let mut globals = HashMap::<&str, (&str, usize)>::new();
+        for (module, (name, object)) in self.modules.iter().enumerate() {
+            for symbol in &object.symbols {
+                let address = starts[module][symbol.section as usize] + symbol.offset;
+                if !symbol.global {
+                    symbols.insert(format!("{name}.{}", symbol.name), address);
+                    continue;
+                }
+                match globals.get(symbol.name.as_str()) {
+                    | Some((first, _)) => errors.push(LinkError::DuplicateSymbol {
+                        name: symbol.name.clone(),
+                        first: first.to_string(),
+                        second: name.clone(),
+                    }),
+                    | None => {
+                        globals.insert(&symbol.name, (name, address));
+                        symbols.insert(symbol.name.clone(), address);
+                    }
+                }
+            }
+        }
+
+        let mut sections = self.modules.iter().map(|(_, object)| object.sections.clone()).collect::<Vec<_>>();
+        for (module, (name, object)) in self.modules.iter().enumerate() {
+            for relocation in &object.relocations {
+                let target = match &relocation.target {
+                    | Target::Section(section) => starts[module][*section as usize],
+                    | Target::External(symbol) => match globals.get(symbol.as_str()) {
+                        | Some((_, address)) => *address,
+                        | None => {
+                            let error = LinkError::UnresolvedSymbol { name: symbol.clone(), module: name.clone() };
+                            if !errors.contains(&error) {
+                                errors.push(error);
+                            }
+                            continue;
+                        }
+                    },
+                };
+                match relocation.resolve(target) {
+                    | Ok(byte) => sections[module][relocation.section as usize][relocation.offset] = byte,
+                    | Err(address) => errors.push(LinkError::AddressOutOfRange { module: name.clone(), address }),
+                }
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let segments = ranges
+            .into_iter()
+            .filter(|(_, range)| !range.is_empty())
+            .map(|(section, range)| Segment {
+                section,
+                address: range.start,
+                bytes: sections.iter().flat_map(|module| module[section as usize].iter().copied()).collect(),
+            })
+            .collect();
+        Ok(Program { segments, symbols })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assembler::assemble_object;
+    use crate::instructions::Instruction;
+    use crate::memory::MemoryBlock;
+
+    use super::*;
+
+    const APPLICATION: &str = "
+        .extern double
+        .global main
+        main:   LOADIMM r0, value
+                JUMP double
+        .data
+        value:  .byte 21
+        .bss
+        scratch: .space 2
+    ";
+
+    const LIBRARY: &str = "
+        .global double
+        double: ADD r0, r0, r0
+                HALT
+    ";
+
+    #[test]
+    fn linked_modules() {
+        let mut linker = Linker::build(Layout { code: 0x10, data: Some(0x40), bss: None });
+        linker.add("app", assemble_object(APPLICATION, 8).unwrap()).add("lib", assemble_object(LIBRARY, 8).unwrap());
+        let program = linker.link().unwrap();
+
+        let sections = program.segments.iter().map(|segment| (segment.section, segment.address)).collect::<Vec<_>>();
+        assert!(sections == [(Section::Code, 0x10), (Section::Data, 0x40), (Section::Bss, 0x41)]);
+        assert!(program.symbols.address("double") == Some(0x15));
+        assert!(program.symbols.address("app.value") == Some(0x40));
+        assert!(program.end() == 0x43);
+
+        let mut mem = MemoryBlock::<u8>::new(0x50);
+        program.load(&mut mem);
+        assert!(mem.read(0x12_usize) == 0x40);
+        assert!(mem.read(0x13_usize) == Instruction::Jump.into() && mem.read(0x14_usize) == 0x15);
+        assert!(mem.read(0x40_usize) == 21);
+    }
+
+    #[test]
+    fn symbol_errors() {
+        let mut linker = Linker::build(Layout::default());
+        linker.add("app", assemble_object(APPLICATION, 8).unwrap());
+        linker.add("first", assemble_object(".global main\nmain: HALT", 8).unwrap());
+        let errors = linker.link().unwrap_err();
+        assert!(errors.contains(&LinkError::DuplicateSymbol {
+            name: "main".into(),
+            first: "app".into(),
+            second: "first".into()
+        }));
+        assert!(errors.contains(&LinkError::UnresolvedSymbol { name: "double".into(), module: "app".into() }));
+    }
+
+    #[test]
+    fn layout_errors() {
+        let mut linker = Linker::build(Layout { code: 0xFC, data: Some(0xFE), bss: None });
+        linker.add("app", assemble_object(APPLICATION, 8).unwrap()).add("lib", assemble_object(LIBRARY, 8).unwrap());
+        let errors = linker.link().unwrap_err();
+        assert!(errors[0] == LinkError::SectionOverlap { first: Section::Code, second: Section::Data });
+        assert!(errors.contains(&LinkError::AddressOutOfRange { module: "app".into(), address: 0x101 }));
+    }
+}
@@ -0,0 +1,831 @@
+use crate::bus::Bus;
+use crate::bus::Cycle;
+use crate::clock::Clock;
+use crate::config::MachineConfig;
+use crate::cpu::Data;
+use crate::cpu::Fault;
+use crate::cpu::Pointer;
+use crate::cpu::ProcState;
+use crate::cpu::Processor;
+use crate::decoder::AddressDecoder;
+use crate::decoder::MapError;
+use crate::decoder::Region;
+use crate::decoder::Unmapped;
+use crate::devices;
+use crate::devices::Console;
+use crate::devices::Device;
+use crate::devices::SharedConsole;
+use crate::image::Image;
+use crate::image::ImageError;
+use crate::interrupts;
+use crate::interrupts::InterruptController;
+use crate::interrupts::InvalidLine;
+use crate::memory::MemoryBlock;
+use crate::syscalls::SyscallContext;
+use crate::syscalls::SyscallOutcome;
+use crate::syscalls::Syscalls;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+
+/// why a run came back to the caller
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    Halted,
+    /// the program asked to end through the exit syscall
+    Exited(Data),
+    CycleLimitReached,
+    Fault(Fault),
+    /// reached an instruction with a breakpoint on it, before running it
+    Breakpoint(Pointer),
+    /// the stop flag was raised or the caller's predicate held
+    ExternalStop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunResult {
+    pub stop: Stop,
+    /// cycles and completed instructions since the last reset
+    pub cycles: usize,
+    pub instructions: usize,
+}
+
+impl RunResult {
+    /// keeps every stop the program or the caller asked for, turns the others into an error
+    pub fn into_result(self) -> Result<RunResult, RunError> {
+        match self.stop {
+            | Stop::CycleLimitReached => Err(RunError::CycleLimitReached { cycles: self.cycles }),
+            | Stop::Fault(fault) => Err(RunError::Fault(fault)),
+            | Stop::Halted | Stop::Exited(_) | Stop::Breakpoint(_) | Stop::ExternalStop => Ok(self),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunError {
+    CycleLimitReached { cycles: usize },
+    Fault(Fault),
+}
+
+impl std::fmt::Display for RunError {
+    fn fmt(&self, frmtr: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            | Self::CycleLimitReached { cycles } => write!(frmtr, "still running after {cycles} cycles"),
+            | Self::Fault(fault) => write!(frmtr, "{fault}"),
+        }
+    }
+}
+
+impl std::error::Error for RunError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            | Self::Fault(fault) => Some(fault),
+            | Self::CycleLimitReached { .. } => None,
+        }
+    }
+}
+
+/// device numbers in the memory map, devices attached by the caller follow on from `ATTACHED`
+const RAM: usize = 0;
+const INTERRUPTS: usize = 1;
+const CONSOLE: usize = 2;
+const ATTACHED: usize = 3;
+
+/// a whole board, the processor, its bus, memory and clock stepped together
+#[derive(Debug)]
+pub struct Machine {
+    pub cpu: Processor,
+    pub bus: Bus<Pointer, Data>,
+    pub ram: MemoryBlock<Data>,
+    pub clock: Clock,
+    config: MachineConfig,
+    entry: Pointer,
+    stack_pointer: Pointer,
+    stack_limit: Pointer,
+    decoder: AddressDecoder,
+    interrupts: InterruptController,
+    console: SharedConsole,
+    devices: Vec<Box<dyn Device>>,
+    syscalls: Syscalls,
+    exit_code: Option<Data>,
+    breakpoints: BTreeSet<Pointer>,
+    stop_flag: Arc<AtomicBool>,
+}
+
+impl Machine {
+    /// maps ram from address 0 and the config's devices by name, refusing a map where any of them overlap
+    pub fn new(config: MachineConfig) -> Result<Self, MapError> {
+        let console = Console::new(std::io::stdin(), std::io::stdout()).shared();
+        Ok(Self {
+            cpu: Processor::new(&config),
+            bus: Default::default(),
+            ram: MemoryBlock::new(config.ram_size),
+            clock: Default::default(),
+            entry: config.initial_program_counter(),
+            stack_pointer: config.initial_stack_pointer(),
+            stack_limit: config.stack_limit.unwrap_or_default(),
+            decoder: memory_map(&config)?,
+            interrupts: Default::default(),
+            console: console.clone(),
+            devices: Vec::new(),
+            syscalls: Syscalls::shared(console),
+            exit_code: Default::default(),
+            breakpoints: Default::default(),
+            stop_flag: Default::default(),
+            config,
+        })
+    }
+
+    /// maps `device` at `base` next to those the config asked for, it keeps its state across resets
+    pub fn attach(
+        &mut self,
+        name: &str,
+        base: usize,
+        size: usize,
+        device: impl Device + 'static,
+    ) -> Result<(), MapError> {
+        self.decoder.map(Region::new(name, base, size, ATTACHED + self.devices.len()))?;
+        self.devices.push(Box::new(device));
+        Ok(())
+    }
+
+    /// serves the console port and the default console syscalls with these streams instead of stdin and stdout
+    pub fn with_console(self, console: Console) -> Self {
+        *self.console.borrow_mut() = console;
+        self
+    }
+
+    pub fn memory_map(&self) -> &[Region] {
+        self.decoder.regions()
+    }
+
+    /// serves syscalls with these handlers instead of the console on stdin and stdout
+    pub fn with_syscalls(mut self, syscalls: Syscalls) -> Self {
+        self.syscalls = syscalls;
+        self
+    }
+
+    pub fn syscalls_mut(&mut self) -> &mut Syscalls {
+        &mut self.syscalls
+    }
+
+    pub fn config(&self) -> &MachineConfig {
+        &self.config
+    }
+
+    /// places an image in memory, a pc or sp set in the config wins over the image
+    pub fn load(&mut self, image: &Image) -> Result<(), ImageError> {
+        let image = image
+            .clone()
+            .with_entry(self.config.program_counter.unwrap_or(image.entry))
+            .with_stack_pointer(self.config.stack_pointer.unwrap_or(image.stack_pointer));
+        image.load(&mut self.ram, &mut self.cpu)?;
+        self.entry = image.entry;
+        self.stack_pointer = image.stack_pointer;
+        self.stack_limit = self.config.stack_limit.unwrap_or(image.stack_limit());
+        self.reset();
+        Ok(())
+    }
+
+    /// puts the processor, bus and clock back to power on, memory keeps its contents
+    pub fn reset(&mut self) {
+        self.cpu = Processor::new(&self.config);
+        self.cpu.program_counter = self.entry;
+        self.cpu.stack_pointer = self.stack_pointer;
+        self.cpu.stack_base = self.stack_pointer;
+        self.cpu.stack_limit = self.stack_limit;
+        self.interrupts = Default::default();
+        self.exit_code = None;
+        self.bus = Default::default();
+        self.clock = Default::default();
+    }
+
+    /// latches a request on an interrupt controller line, as a device would
+    pub fn raise_interrupt(&mut self, line: usize) -> Result<(), InvalidLine> {
+        self.interrupts.raise(line)
+    }
+
+    pub fn interrupts(&self) -> &InterruptController {
+        &self.interrupts
+    }
+
+    pub fn add_breakpoint(&mut self, address: Pointer) {
+        self.breakpoints.insert(address);
+    }
+
+    pub fn remove_breakpoint(&mut self, address: Pointer) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    /// a flag another thread can raise to end the current run at the next cycle
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        self.stop_flag.clone()
+    }
+
+    /// the instruction being run followed by every call site still waiting for a return
+    pub fn backtrace(&self) -> Vec<Pointer> {
+        let current = match self.cpu.state {
+            | ProcState::Idle => self.cpu.program_counter,
+            | _ => self.cpu.instruction_address,
+        };
+        let callers = self.cpu.call_stack.iter().rev().map(|frame| frame.call_site);
+        std::iter::once(current).chain(callers).collect()
+    }
+
+    pub fn halted(&self) -> bool {
+        self.cpu.halted
+    }
+
+    pub fn cycle_limit_reached(&self) -> bool {
+        self.clock.tick >= self.config.cycle_limit
+    }
+
+    pub fn step_cycle(&mut self) {
+        self.cpu.interrupt_request = self.interrupts.next_line();
+        self.cpu.cycle(&mut self.bus);
+        if let Some(line) = self.cpu.interrupt_acknowledge.take() {
+            self.interrupts.acknowledge(line);
+        }
+        if let Some(number) = self.cpu.syscall_request.take() {
+            self.serve(number);
+        }
+        self.decoder.cycle(&mut self.bus, |device, bus| match device {
+            | RAM => self.ram.cycle(bus),
+            | INTERRUPTS => self.interrupts.cycle(bus),
+            | CONSOLE => self.console.borrow_mut().cycle(bus),
+            | attached => self.devices[attached - ATTACHED].cycle(bus),
+        });
+        self.clock.tick += 1;
+    }
+
+    /// runs the host handler right after the instruction asking for it
+    fn serve(&mut self, number: Data) {
+        let mut context = SyscallContext {
+            registers: &mut self.cpu.registers,
+            memory: &mut self.ram,
+            cycles: self.clock.tick,
+            instructions: self.cpu.retired,
+        };
+        match self.syscalls.dispatch(number, &mut context) {
+            | Ok(SyscallOutcome::Continue) => {}
+            | Ok(SyscallOutcome::Exit(code)) => {
+                self.exit_code = Some(code);
+                self.cpu.halted = true;
+            }
+            | Err(kind) => self.cpu.raise(kind),
+        }
+    }
+
+    /// cycles until the processor is back between instructions, returns how many it took
+    pub fn step_instruction(&mut self) -> usize {
+        let start = self.clock.tick;
+        while !self.halted() && !self.cycle_limit_reached() {
+            self.step_cycle();
+            if let ProcState::Idle = self.cpu.state {
+                break;
+            }
+        }
+        self.clock.tick - start
+    }
+
+    /// steps until the program stops or `predicate` holds before a cycle,
+    /// a breakpoint under the starting instruction is stepped over so a stopped run can resume
+    pub fn run_until(&mut self, mut predicate: impl FnMut(&Machine) -> bool) -> RunResult {
+        let start = self.clock.tick;
+        let stop = loop {
+            if let Some(fault) = self.cpu.fault {
+                break Stop::Fault(fault);
+            }
+            if let Some(code) = self.exit_code {
+                break Stop::Exited(code);
+            }
+            if self.halted() {
+                break Stop::Halted;
+            }
+            if self.cycle_limit_reached() {
+                break Stop::CycleLimitReached;
+            }
+            if self.stop_flag.swap(false, Ordering::Relaxed) || predicate(self) {
+                break Stop::ExternalStop;
+            }
+            let boundary = matches!(self.cpu.state, ProcState::Idle);
+            if boundary && self.clock.tick != start && self.breakpoints.contains(&self.cpu.program_counter) {
+                break Stop::Breakpoint(self.cpu.program_counter);
+            }
+            self.step_cycle();
+        };
+        RunResult { stop, cycles: self.clock.tick, instructions: self.cpu.retired }
+    }
+
+    pub fn run(&mut self) -> RunResult {
+        self.run_until(|_| false)
+    }
+}
+
+/// ram and every device the config names, the interrupt controller and console only answer once mapped
+fn memory_map(config: &MachineConfig) -> Result<AddressDecoder, MapError> {
+    let unmapped = config.open_bus.map_or(Unmapped::BusError, Unmapped::OpenBus);
+    let mut regions = vec![Region::new("ram", 0, config.ram_size, RAM)];
+    for mapping in &config.devices {
+        let device = match mapping.name.as_str() {
+            | interrupts::DEVICE_NAME => INTERRUPTS,
+            | devices::CONSOLE => CONSOLE,
+            | _ => return Err(MapError::UnknownDevice { name: mapping.name.clone() }),
+        };
+        regions.push(Region::new(&mapping.name, mapping.base, mapping.size, device));
+    }
+    AddressDecoder::build(regions, unmapped)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assembler::assemble_object;
+    use crate::config::DeviceMapping;
+    use crate::cpu::FaultKind;
+    use crate::instructions::Instruction;
+    use crate::linker::Layout;
+    use crate::linker::Linker;
+    use crate::memory::Addressable;
+    use crate::testing::Capture;
+
+    use super::*;
+
+    fn image(source: &str) -> Image {
+        let mut linker = Linker::build(Layout::default());
+        linker.add("test", assemble_object(source, 16).unwrap());
+        let program = linker.link().unwrap();
+        Image::from_program(&program, 63).unwrap().with_symbols(program.symbols)
+    }
+
+    fn machine(source: &str) -> Machine {
+        let mut machine = Machine::new(MachineConfig { ram_size: 64, cycle_limit: 500, ..Default::default() }).unwrap();
+        machine.load(&image(source)).unwrap();
+        machine
+    }
+
+    #[test]
+    fn single_steps() {
+        let mut machine = machine("LOADIMM r0, 3\nLOADIMM r1, 4\nHALT");
+        assert!(machine.cpu.stack_pointer == 63);
+        assert!(machine.step_instruction() == machine.clock.tick);
+        assert!(machine.cpu.registers.read(0_usize) == 3 && machine.cpu.registers.read(1_usize) == 0);
+        assert!(machine.cpu.program_counter == 3);
+
+        let result = machine.run();
+        assert!(result.stop == Stop::Halted && result.instructions == 3 && result.cycles == machine.clock.tick);
+        assert!(machine.cpu.registers.read(1_usize) == 4);
+        assert!(machine.step_instruction() == 0);
+    }
+
+    #[test]
+    fn predicate_and_reset() {
+        let mut machine = machine("top: INCREMENT r0\nJUMP top");
+        let result = machine.run_until(|machine| machine.cpu.registers.read(0_usize) == 5);
+        assert!(result.stop == Stop::ExternalStop && machine.cpu.registers.read(0_usize) == 5);
+
+        let result = machine.run();
+        assert!(result.stop == Stop::CycleLimitReached && result.cycles == 500);
+        assert!(result.into_result() == Err(RunError::CycleLimitReached { cycles: 500 }));
+        machine.reset();
+        assert!(machine.clock.tick == 0 && machine.cpu.program_counter == 0 && machine.cpu.retired == 0);
+        assert!(machine.cpu.registers.read(0_usize) == 0);
+    }
+
+    #[test]
+    fn config_overrides_image() {
+        let image = image("HALT").with_stack_pointer(0x1FF);
+        let mut small = Machine::new(MachineConfig { ram_size: 0x100, ..Default::default() }).unwrap();
+        let error = small.load(&image).unwrap_err();
+        assert!(error == ImageError::RegisterOutOfBounds { register: "stack pointer", address: 0x1FF, size: 0x100 });
+
+        let config = MachineConfig { ram_size: 0x100, stack_pointer: Some(0xFF), ..Default::default() };
+        let mut overridden = Machine::new(config).unwrap();
+        overridden.load(&image).unwrap();
+        assert!(overridden.cpu.stack_pointer == 0xFF && overridden.cpu.stack_base == 0xFF);
+    }
+
+    #[test]
+    fn breakpoints_and_stop_flag() {
+        let mut machine = machine("top: INCREMENT r0\nJUMP top");
+        machine.add_breakpoint(2);
+        for count in 1..=3 {
+            let result = machine.run();
+            assert!(result.stop == Stop::Breakpoint(2) && result.instructions == 2 * count - 1);
+            assert!(machine.cpu.registers.read(0_usize) == count as Data);
+        }
+        assert!(machine.remove_breakpoint(2) && !machine.remove_breakpoint(2));
+
+        machine.stop_flag().store(true, Ordering::Relaxed);
+        assert!(machine.run().stop == Stop::ExternalStop);
+        assert!(machine.run().stop == Stop::CycleLimitReached);
+    }
+
+    #[test]
+    fn arithmetic_flags() {
+        let mut increment = machine("LOADIMM r0, 0x7F\nINCREMENT r0\nHALT");
+        increment.run();
+        let flags = &increment.cpu.flags;
+        assert!(flags.overflow && flags.negative && !flags.carry && !flags.zero);
+
+        let mut compare = machine("LOADIMM r1, 3\nLOADIMM r2, 5\nCOMPARE r1, r2\nHALT");
+        compare.run();
+        let flags = &compare.cpu.flags;
+        assert!(flags.less && flags.carry && flags.negative && !flags.overflow && !flags.zero);
+    }
+
+    #[test]
+    fn conditional_jumps() {
+        let taken = |a: Data, b: Data, mnemonic: &str| {
+            let source = format!(
+                "LOADIMM r0, {a}\nLOADIMM r1, {b}\nCOMPARE r0, r1\n{mnemonic} yes\nHALT\nyes: LOADIMM r2, 1\nHALT"
+            );
+            let mut machine = machine(&source);
+            assert!(machine.run().stop == Stop::Halted);
+            machine.cpu.registers.read(2_usize) == 1
+        };
+        let table = [
+            ("JUMPEQ", [false, true, false]),
+            ("JUMPNE", [true, false, true]),
+            ("JUMPLT", [true, false, false]),
+            ("JUMPGT", [false, false, true]),
+            ("JUMPLE", [true, true, false]),
+            ("JUMPGE", [false, true, true]),
+            ("JUMPCARRY", [true, false, false]),
+            ("JUMPNEGATIVE", [true, false, false]),
+            ("JUMPLTS", [true, false, true]),
+            ("JUMPGTS", [false, false, false]),
+            ("JUMPLES", [true, true, true]),
+            ("JUMPGES", [false, true, false]),
+            ("JUMPIFZERO", [true, false, true]),
+        ];
+        // 3 against 5, 7 against 7, and 0x80 against 1 which is greater unsigned but less signed
+        for (mnemonic, expected) in table {
+            let results = [taken(3, 5, mnemonic), taken(7, 7, mnemonic), taken(0x80, 1, mnemonic)];
+            assert!(results == expected, "{mnemonic} gave {results:?}");
+        }
+        assert!(taken(0x80, 1, "JUMPOVERFLOW") && !taken(3, 5, "JUMPOVERFLOW"));
+    }
+
+    #[test]
+    fn nested_calls() {
+        let source = "
+            LOADIMM r0, 1
+            CALL outer
+            HALT
+            outer:  LOADIMM r1, inner
+                    CALLREG r1
+                    INCREMENT r0
+                    RET
+            inner:  INCREMENT r0
+                    RET
+        ";
+        let image = image(source);
+        let inner = image.symbols.as_ref().and_then(|symbols| symbols.address("test.inner")).unwrap();
+        let mut machine = Machine::new(MachineConfig { ram_size: 64, ..Default::default() }).unwrap();
+        machine.load(&image).unwrap();
+        machine.add_breakpoint(inner as Pointer);
+        assert!(machine.run().stop == Stop::Breakpoint(14));
+        assert!(machine.backtrace() == [14, 9, 3] && machine.cpu.stack_pointer == 59);
+        assert!(machine.ram.read(60_usize) == 11 && machine.ram.read(61_usize) == 0 && machine.ram.read(62_usize) == 5);
+
+        assert!(machine.run().stop == Stop::Halted);
+        assert!(machine.cpu.registers.read(0_usize) == 3 && machine.cpu.program_counter == 6);
+        assert!(machine.cpu.call_stack.is_empty() && machine.cpu.stack_pointer == 63);
+    }
+
+    #[test]
+    fn far_calls() {
+        let source = "
+                    CALLW far
+                    HALT
+                    .org 0x140
+            far:    CALLW reset
+            reset:  LOADIMMW r2:r3, 63
+                    SETSP r2:r3
+                    JUMPW done
+                    .org 0x1A0
+            done:   LOADIMM r0, 7
+                    HALT
+        ";
+        let image = image(source);
+        let reset = image.symbols.as_ref().and_then(|symbols| symbols.address("test.reset")).unwrap();
+        let mut machine = Machine::new(MachineConfig { ram_size: 0x200, ..Default::default() }).unwrap();
+        machine.load(&image).unwrap();
+        machine.add_breakpoint(reset as Pointer);
+        assert!(machine.run().stop == Stop::Breakpoint(0x143));
+        assert!(machine.backtrace() == [0x143, 0x140, 0] && machine.cpu.stack_pointer == 59);
+
+        assert!(machine.run().stop == Stop::Halted);
+        assert!(machine.cpu.registers.read(0_usize) == 7 && machine.cpu.program_counter == 0x1A4);
+        assert!(machine.cpu.call_stack.is_empty() && machine.cpu.stack_pointer == 63);
+    }
+
+    #[test]
+    fn bitwise_instructions() {
+        let source = "
+            LOADIMM r0, 0b1100
+            LOADIMM r1, 0b1010
+            AND r2, r0, r1
+            OR r3, r0, r1
+            XOR r4, r0, r1
+            NOT r5, r0
+            SAR r6, r5, 2
+            SHL r7, r0, 5
+            RCL r1
+            BSET r0, 0
+            BCLR r0, 3
+            BTST r0, 1
+            HALT
+        ";
+        let mut machine = machine(source);
+        assert!(machine.run().stop == Stop::Halted);
+        let registers = (0..8_usize).map(|index| machine.cpu.registers.read(index)).collect::<Vec<Data>>();
+        assert!(registers == [0b0101, 0b10101, 0b1000, 0b1110, 0b0110, 0b1111_0011, 0b1111_1100, 0b1000_0000]);
+        let flags = &machine.cpu.flags;
+        assert!(flags.zero && !flags.carry && !flags.negative);
+    }
+
+    #[test]
+    fn memory_addressing() {
+        let source = "
+                LOADIMM r1, source
+                LOADIMM r2, target
+                LOADIMM r3, 4
+            copy:
+                LOADIND r0, [r1]
+                STOREIND [r2+1], r0
+                INCREMENT r1
+                INCREMENT r2
+                DECREMENT r3
+                COMPARE r3, r5
+                JUMPNE copy
+                LOADIND r4, [r2-1]
+                STORE 0x20, r4
+                LOADIMM r6, 0
+                LOADIMM r7, 0x30
+                STOREPAIR [r6:r7+2], r4
+                LOADPAIR r5, [r6:r7+2]
+                HALT
+            .data
+            source: .byte 1, 2, 3, 4
+            target: .space 5
+        ";
+        let mut copier = machine(source);
+        assert!(copier.run().stop == Stop::Halted);
+        let target = copier.cpu.registers.read(2_usize) as usize - 4;
+        let copied = (target..target + 5).map(|address| copier.ram.read(address)).collect::<Vec<Data>>();
+        assert!(copied == [0, 1, 2, 3, 4]);
+        assert!(copier.ram.read(0x20_usize) == 3 && copier.ram.read(0x32_usize) == 3);
+        assert!(copier.cpu.registers.read(5_usize) == 3);
+
+        let mut beyond = machine("LOADIMM r0, 1\nLOADIMM r1, 0\nLOADPAIR r2, [r0:r1]\nHALT");
+        let fault = Fault { kind: FaultKind::BusError(0x100), program_counter: 6 };
+        assert!(beyond.run().stop == Stop::Fault(fault));
+    }
+
+    #[test]
+    fn wide_counters() {
+        let source = "
+                LOADIMMW r0:r1, 300
+                LOADIMMW r2:r3, 0
+                LOADIMMW r4:r5, 0
+            count:
+                INCW r2:r3
+                DECW r0:r1
+                COMPAREW r0:r1, r4:r5
+                JUMPNE count
+                LOADIMMW r4:r5, 0x0102
+                ADDW r6:r7, r2:r3, r4:r5
+                SUBW r0:r1, r4:r5, r2:r3
+                HALT
+        ";
+        let mut counter = machine(source);
+        counter.config.cycle_limit = 20_000;
+        assert!(counter.run().stop == Stop::Halted);
+        assert!(counter.cpu.read_pair(2) == Ok(300) && counter.cpu.read_pair(6) == Ok(300 + 0x0102));
+        assert!(counter.cpu.read_pair(0) == Ok(0x0102_u16.wrapping_sub(300)));
+        assert!(counter.cpu.flags.carry && counter.cpu.flags.negative);
+    }
+
+    #[test]
+    fn stack_frames() {
+        let source = "
+                GETPC r6:r7
+                LOADIMM r0, 7
+                PUSH r0
+                ADJSP -2
+                STORESP [sp+1], r0
+                LOADSP r1, [sp+3]
+                GETSP r2:r3
+                INCW r2:r3
+                LOADPAIR r4, [r2:r3]
+                ADJSP 3
+                HALT
+        ";
+        let mut frames = machine(source);
+        assert!(frames.run().stop == Stop::Halted);
+        assert!(frames.cpu.read_pair(6) == Ok(2) && frames.cpu.stack_pointer == 63);
+        assert!(frames.cpu.registers.read(1_usize) == 7 && frames.cpu.registers.read(4_usize) == 7);
+        assert!(frames.cpu.read_pair(2) == Ok(61));
+
+        let mut moved = machine("LOADIMMW r0:r1, 0x30\nLOADIMMW r2:r3, 0x38\nSETSTACK r0:r1, r2:r3\nPUSH r0\nHALT");
+        assert!(moved.run().stop == Stop::Halted);
+        assert!(moved.cpu.stack_pointer == 0x37 && moved.ram.read(0x38_usize) == 0);
+        assert!(moved.cpu.stack_limit == 0x30 && moved.cpu.stack_base == 0x38);
+    }
+
+    #[test]
+    fn stack_limits() {
+        let run = |source: &str| {
+            let mut machine = machine(source);
+            machine.config.cycle_limit = 5000;
+            (machine.run(), machine)
+        };
+        let fault = |source: &str| match run(source).0.into_result() {
+            | Err(RunError::Fault(fault)) => fault.kind,
+            | other => panic!("{source} stopped with {other:?}"),
+        };
+        assert!(fault("top: PUSH r0\nJUMP top") == FaultKind::StackOverflow);
+        assert!(fault("top: CALL top") == FaultKind::StackOverflow);
+        assert!(fault("ADJSP -64") == FaultKind::StackOverflow);
+        assert!(fault("ADJSP 1") == FaultKind::StackUnderflow);
+        assert!(fault("LOADIMMW r0:r1, 1\nSETSP r0:r1") == FaultKind::StackOverflow);
+
+        let (_, full) = run("top: PUSH r0\nJUMP top");
+        assert!(full.cpu.stack_pointer == full.cpu.stack_limit && full.ram.read(0_usize) == Instruction::Push as Data);
+    }
+
+    #[test]
+    fn vectored_interrupts() {
+        let source = "
+                .global table, tick, poke
+                LOADIMM r0, 0b1111_1110
+                STORE 0x41, r0
+                EI
+            idle:
+                INCREMENT r1
+                COMPARE r3, r5
+                JUMPEQ idle
+                HALT
+            tick:
+                INCREMENT r2
+                LOADIMM r4, 0b100
+                STORE 0x42, r4
+                RETI
+            poke:
+                INCREMENT r3
+                RETI
+            table: .byte poke, 0, tick, 0, poke, 0
+        ";
+        let image = image(source);
+        let address = |name| image.symbols.as_ref().and_then(|symbols| symbols.address(name)).unwrap() as Pointer;
+        let config = MachineConfig {
+            ram_size: 64,
+            cycle_limit: 2000,
+            interrupt_table: Some(address("table")),
+            devices: vec![DeviceMapping { name: interrupts::DEVICE_NAME.into(), base: 0x40, size: 4 }],
+            ..Default::default()
+        };
+        let mut machine = Machine::new(config).unwrap();
+        machine.load(&image).unwrap();
+        machine.run_until(|machine| machine.cpu.registers.read(1_usize) == 3);
+        assert!(machine.interrupts().mask() == 0b1111_1110);
+        machine.raise_interrupt(1).unwrap();
+        machine.raise_interrupt(0).unwrap();
+        assert!(machine.raise_interrupt(8) == Err(InvalidLine(8)));
+        machine.run_until(|machine| matches!(machine.cpu.state, ProcState::Interrupt(_)));
+        machine.step_instruction();
+        assert!(machine.cpu.program_counter == address("tick") && !machine.cpu.flags.interrupt_enable);
+        assert!(machine.backtrace()[1] == machine.cpu.call_stack[0].call_site);
+
+        assert!(machine.run().stop == Stop::Halted);
+        assert!(machine.cpu.registers.read(2_usize) == 1 && machine.cpu.registers.read(3_usize) == 1);
+        assert!(machine.interrupts().pending() == 0b1 && machine.cpu.flags.interrupt_enable);
+        assert!(machine.cpu.stack_pointer == 63 && machine.cpu.call_stack.is_empty());
+    }
+
+    #[test]
+    fn host_syscalls() {
+        let source = "
+                LOADIMM r0, 20
+                SYSCALL 7
+                SYSCALL 7
+                LOADIMM r1, 4
+                SYSCALL 0
+                HALT
+        ";
+        let mut syscalls = Syscalls::default();
+        syscalls.register(crate::syscalls::EXIT, |context| Ok(SyscallOutcome::Exit(*context.register(1)?)));
+        syscalls.register(7, |context| {
+            let value = *context.register(0)?;
+            *context.memory.write(value as usize) = value;
+            *context.register(0)? = value + context.instructions as Data;
+            Ok(SyscallOutcome::Continue)
+        });
+        let mut served = machine(source).with_syscalls(syscalls);
+        let result = served.run();
+        assert!(result.stop == Stop::Exited(4) && result.instructions == 5 && served.halted());
+        assert!(served.ram.read(20_usize) == 20 && served.ram.read(22_usize) == 22);
+        assert!(served.cpu.registers.read(0_usize) == 25);
+
+        let mut unserved = machine("SYSCALL 9").with_syscalls(Syscalls::default());
+        let fault = Fault { kind: FaultKind::InvalidSyscall(9), program_counter: 0 };
+        assert!(unserved.run().stop == Stop::Fault(fault));
+    }
+
+    #[test]
+    fn shared_console() {
+        let source = "
+                SYSCALL 2
+                SYSCALL 1
+                LOADMEM r0, 0x80
+                STORE 0x80, r0
+                SYSCALL 2
+                HALT
+        ";
+        let devices = vec![DeviceMapping { name: devices::CONSOLE.into(), base: 0x80, size: 2 }];
+        let config = MachineConfig { ram_size: 64, devices, ..Default::default() };
+        let capture = Capture::default();
+        let mut machine = Machine::new(config).unwrap().with_console(Console::new(&b"ab"[..], capture.clone()));
+        machine.load(&image(source)).unwrap();
+        assert!(machine.run().stop == Stop::Halted);
+        assert!(capture.0.borrow().as_slice() == b"ab" && machine.cpu.registers.read(1_usize) == 1);
+    }
+
+    #[test]
+    fn memory_mapped_devices() {
+        let source = "
+                LOADMEM r0, 0xC0
+                STORE 0x80, r0
+                LOADMEM r1, 0x80
+                STORE 0x80, r1
+                LOADMEM r2, 0x90
+                STORE 0xC1, r2
+        ";
+        let devices = vec![DeviceMapping { name: devices::CONSOLE.into(), base: 0x80, size: 2 }];
+        let config = MachineConfig { ram_size: 64, open_bus: Some(0xEE), devices, ..Default::default() };
+        let capture = Capture::default();
+        let mut mapped = Machine::new(config.clone()).unwrap().with_console(Console::new(&b"i"[..], capture.clone()));
+        mapped.attach("rom", 0xC0, 2, devices::Rom::new(b"o")).unwrap();
+        assert!(mapped.memory_map().iter().map(|region| region.name.as_str()).eq(["ram", "console", "rom"]));
+        mapped.load(&image(source)).unwrap();
+
+        let fault = Fault { kind: FaultKind::BusError(0xC1), program_counter: 15 };
+        assert!(mapped.run().stop == Stop::Fault(fault));
+        assert!(capture.0.borrow().as_slice() == b"oi" && mapped.cpu.registers.read(2_usize) == 0xEE);
+
+        let error = mapped.attach("low", 0x3F, 2, devices::Rom::new(&[0, 0])).unwrap_err();
+        assert!(error == MapError::Overlap { first: "ram".into(), second: "low".into() });
+        let unknown = vec![DeviceMapping { name: "disk".into(), base: 0x200, size: 2 }];
+        let error = Machine::new(MachineConfig { devices: unknown, ..config }).unwrap_err();
+        assert!(error == MapError::UnknownDevice { name: "disk".into() });
+    }
+
+    #[test]
+    fn halting_faults() {
+        let fault = |source: &str| match machine(source).run().into_result() {
+            | Err(RunError::Fault(fault)) => fault,
+            | other => panic!("{source} stopped with {other:?}"),
+        };
+        let divide = fault("LOADIMM r1, 0\nDIV r0, r0, r1");
+        assert!(divide == Fault { kind: FaultKind::DivideByZero, program_counter: 3 });
+        assert!(fault(".byte 0xEE").kind == FaultKind::InvalidOpcode(0xEE));
+        assert!(fault("COPY r0, r12").kind == FaultKind::InvalidRegister(12));
+        assert!(fault("LOADMEM r0, 0x80").kind == FaultKind::BusError(0x80));
+        assert!(fault("POP r0").kind == FaultKind::StackUnderflow);
+        assert!(fault("LOADIMM r0, 1\nPUSH r0\nRET").kind == FaultKind::StackUnderflow);
+        assert!(fault("top: JUMP 0x70").to_string() == "nothing answers at 0x0070 at 0x0070");
+
+        let mut machine = machine("PUSH r0");
+        machine.cpu.stack_pointer = 0;
+        assert!(machine.run().stop == Stop::Fault(Fault { kind: FaultKind::StackOverflow, program_counter: 0 }));
+    }
+
+    #[test]
+    fn vectored_fault() {
+        let source = "
+                .global handler
+                LOADIMM r1, 0
+                DIV r0, r0, r1
+                LOADIMM r3, 9
+                HALT
+            handler:
+                POP r2
+                POP r4
+                LOADIMM r5, 4
+                ADD r4, r4, r5
+                PUSH r4
+                RET
+        ";
+        let image = image(source);
+        let handler = image.symbols.as_ref().and_then(|symbols| symbols.address("handler")).unwrap();
+        let config = MachineConfig { ram_size: 64, fault_vector: Some(handler as Pointer), ..Default::default() };
+        let mut machine = Machine::new(config).unwrap();
+        machine.load(&image).unwrap();
+        assert!(machine.run().stop == Stop::Halted);
+        assert!(machine.cpu.registers.read(2_usize) == FaultKind::DivideByZero.code());
+        assert!(machine.cpu.registers.read(4_usize) == 7 && machine.cpu.registers.read(3_usize) == 9);
+        assert!(machine.cpu.stack_pointer == 63 && machine.cpu.call_stack.is_empty());
+    }
+}
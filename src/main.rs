@@ -1,82 +1,613 @@
-mod assembler;
-mod bus;
-mod clock;
-mod cpu;
-mod instructions;
-mod memory;
-
-use assembler::ProgramAssembler;
-use bus::Bus;
-use clock::Clock;
-use cpu::_processor_run_debug;
-use cpu::Data;
-use cpu::Pointer;
-use cpu::Processor;
-use cpu::processor_run;
-use instructions::Instruction;
-use memory::MemoryBlock;
-
-const RAM_SIZE: usize = 512;
-const CYCLE_LIMIT: usize = 100_000_000;
-const REG_COUNT: usize = 8;
-
-fn main() {
-    let mut processor = Processor::<REG_COUNT>::default();
-    let mut clock = Clock::default();
-    let mut ram = MemoryBlock::<RAM_SIZE, Data>::default();
-    let mut bus = Bus::<Pointer, Data>::default();
-
-    let mut assembler = ProgramAssembler::build(&mut ram);
-    // first 10 fibonacci numbers
-    assembler.assemble_program(vec![
-        vec![Instruction::LoadImm.into(), 3, 10],
-        vec![Instruction::LoadImm.into(), 1, 0],
-        vec![Instruction::LoadImm.into(), 2, 1],
-        vec![Instruction::LoadImm.into(), 4, 0],
-        vec![Instruction::Push.into(), 1],
-        vec![Instruction::Push.into(), 2],
-        vec![Instruction::Add.into(), 0, 1, 2],
-        vec![Instruction::Push.into(), 0],
-        vec![Instruction::Copy.into(), 1, 2],
-        vec![Instruction::Copy.into(), 2, 0],
-        vec![Instruction::Decrement.into(), 3],
-        vec![Instruction::Compare.into(), 3, 4],
-        vec![Instruction::JumpIfZero.into(), 16],
-        vec![Instruction::Halt.into()],
-    ]);
-    // 5 factorial
-    assembler.assemble_program(vec![
-        vec![Instruction::LoadImm.into(), 0, 5],
-        vec![Instruction::LoadImm.into(), 1, 1],
-        vec![Instruction::LoadImm.into(), 2, 0],
-        vec![Instruction::Mul.into(), 1, 1, 0],
-        vec![Instruction::Decrement.into(), 0],
-        vec![Instruction::Compare.into(), 0, 2],
-        vec![Instruction::JumpIfZero.into(), 9],
-        vec![Instruction::Push.into(), 1],
-        vec![Instruction::Halt.into()],
-    ]);
-    // function jump and return
-    assembler.assemble_program(vec![
-        /* main */
-        vec![Instruction::LoadImm.into(), 0, 33],
-        vec![Instruction::LoadImm.into(), 1, 10],
-        vec![Instruction::Push.into(), 1],
-        vec![Instruction::Jump.into(), 11],
-        vec![Instruction::Halt.into()],
-        /* function to double a number */
-        vec![Instruction::LoadImm.into(), 1, 2],
-        vec![Instruction::Mul.into(), 0, 0, 1],
-        vec![Instruction::Ret.into()],
-    ]);
-
-    let cycle_start = std::time::Instant::now();
-    _processor_run_debug(&mut processor, &mut ram, &mut bus, &mut clock);
-    processor_run(&mut processor, &mut ram, &mut bus, &mut clock);
-    let elapsed = cycle_start.elapsed().as_secs_f32();
-    println!("\x1b[2J\x1b[0H");
-    dbg!(&ram);
-    dbg!(&processor);
-    dbg!(&elapsed);
-    dbg!(&clock);
+use pet_processor::assembler::AssemblyError;
+use pet_processor::assembler::assemble_object;
+use pet_processor::config::ConfigError;
+use pet_processor::config::MachineConfig;
+use pet_processor::cpu::ADDRESS_SPACE;
+use pet_processor::cpu::Data;
+use pet_processor::cpu::Pointer;
+use pet_processor::decoder::MapError;
+use pet_processor::disassembler::Disassembler;
+use pet_processor::image::Image;
+use pet_processor::image::ImageError;
+use pet_processor::linker::Layout;
+use pet_processor::linker::LinkError;
+use pet_processor::linker::Linker;
+use pet_processor::linker::Program;
+use pet_processor::machine::Machine;
+use pet_processor::machine::Stop;
+use pet_processor::memory::Addressable;
+use pet_processor::memory::MemoryBlock;
+use pet_processor::number;
+use pet_processor::object::Object;
+use pet_processor::object::ObjectError;
+use pet_processor::object::Section;
+use pet_processor::symbols::SymbolError;
+use pet_processor::symbols::SymbolTable;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+usage: pet-processor <command> [options]
+
+commands:
+    assemble <file>... -o <output>   assemble and link sources and objects into an image
+        --object                     write one unlinked object module instead
+        --base <addr>                start of the code section, data and bss follow it
+        --data <addr>, --bss <addr>  fixed starts for the data and bss sections
+        --map <file>                 also write the symbol map
+        --no-symbols                 leave the symbol section out of the image
+        --config <file>              machine the image is built for, the flags below override it
+        --ram <bytes>                memory size, the stack starts at its top
+        --sp <addr>                  initial stack pointer stored in the image
+        --registers <count>          registers the sources may name
+    run <file>...                    run an image, or link sources and objects first
+        --config <file>              machine settings, the flags below override it
+        --ram <bytes>                memory size, at most 65536
+        --registers <count>          number of processor registers
+        --pc <addr>, --sp <addr>     start here instead of where the image says
+        --cycles <count>             stop after this many cycles
+        --dump <text|json|none>      format of the final machine state
+        --trace                      redraw the machine every cycle
+        --base, --data, --bss        layout used when linking
+    disasm <image>                   list every segment of an image
+        --map <file>                 take labels from a symbol map instead of the image
+
+exit codes: 0 halted, 1 bad input, 2 fault, 3 cycle limit reached";
+
+/// images start with this, anything else handed to `run` is linked first
+const IMAGE_MAGIC: &[u8] = b"PETI";
+
+#[derive(Debug)]
+enum CliError {
+    Usage(String),
+    Io { path: PathBuf, error: std::io::Error },
+    Assembly { path: PathBuf, error: AssemblyError },
+    Object { path: PathBuf, error: ObjectError },
+    Symbols { path: PathBuf, error: SymbolError },
+    Link(Vec<LinkError>),
+    Image(ImageError),
+    Config { path: Option<PathBuf>, error: ConfigError },
+    Map(MapError),
+}
+
+impl std::fmt::Display for CliError {
+    fn fmt(&self, frmtr: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            | Self::Usage(message) => write!(frmtr, "{message}"),
+            | Self::Io { path, error } => write!(frmtr, "{}: {error}", path.display()),
+            | Self::Assembly { path, error } => write!(frmtr, "{}: {error}", path.display()),
+            | Self::Object { path, error } => write!(frmtr, "{}: {error}", path.display()),
+            | Self::Symbols { path, error } => write!(frmtr, "{}: {error}", path.display()),
+            | Self::Link(errors) => {
+                let errors = errors.iter().map(LinkError::to_string).collect::<Vec<String>>();
+                write!(frmtr, "{}", errors.join("\n"))
+            }
+            | Self::Image(error) => write!(frmtr, "{error}"),
+            | Self::Config { path: Some(path), error } => write!(frmtr, "{}: {error}", path.display()),
+            | Self::Config { path: None, error } => write!(frmtr, "{error}"),
+            | Self::Map(error) => write!(frmtr, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Assemble,
+    Run,
+    Disassemble,
+}
+
+impl Command {
+    fn accepts(&self, option: &str) -> bool {
+        match self {
+            | Command::Assemble => [
+                "-o",
+                "--object",
+                "--base",
+                "--data",
+                "--bss",
+                "--map",
+                "--no-symbols",
+                "--config",
+                "--ram",
+                "--sp",
+                "--registers",
+            ]
+            .contains(&option),
+            | Command::Run => [
+                "--config",
+                "--ram",
+                "--registers",
+                "--pc",
+                "--sp",
+                "--cycles",
+                "--dump",
+                "--trace",
+                "--base",
+                "--data",
+                "--bss",
+            ]
+            .contains(&option),
+            | Command::Disassemble => option == "--map",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Dump {
+    #[default]
+    Text,
+    Json,
+    None,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Options {
+    inputs: Vec<PathBuf>,
+    output: Option<PathBuf>,
+    map: Option<PathBuf>,
+    layout: Layout,
+    config: Option<PathBuf>,
+    /// machine settings given as flags, applied over the config file in order
+    settings: Vec<(&'static str, String)>,
+    dump: Dump,
+    object: bool,
+    symbols: bool,
+    trace: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            inputs: Vec::new(),
+            output: None,
+            map: None,
+            layout: Layout::default(),
+            config: None,
+            settings: Vec::new(),
+            dump: Dump::default(),
+            object: false,
+            symbols: true,
+            trace: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Halted,
+    Exited(u8),
+    Fault,
+    CycleLimit,
+}
+
+impl Status {
+    fn code(&self) -> u8 {
+        match self {
+            | Status::Halted => 0,
+            | Status::Exited(code) => *code,
+            | Status::Fault => 2,
+            | Status::CycleLimit => 3,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            | Status::Halted => "halted",
+            | Status::Exited(_) => "exited",
+            | Status::Fault => "fault",
+            | Status::CycleLimit => "cycle limit",
+        }
+    }
+}
+
+/// machine state once a run has stopped, every dump format reads from this
+#[derive(Debug)]
+struct Snapshot {
+    status: Status,
+    cycles: usize,
+    instructions: usize,
+    program_counter: Pointer,
+    stack_pointer: Pointer,
+    flags: [(&'static str, bool); 6],
+    registers: Vec<Data>,
+    memory: Vec<Data>,
+}
+
+impl Snapshot {
+    fn text(&self) -> String {
+        let mut text =
+            format!("{} after {} cycles, {} instructions\n", self.status.name(), self.cycles, self.instructions);
+        let flags = self.flags.iter().filter(|(_, set)| *set).map(|(name, _)| *name).collect::<Vec<&str>>();
+        text.push_str(&format!(
+            "pc {:#06x}  sp {:#06x}  flags [{}]\n",
+            self.program_counter,
+            self.stack_pointer,
+            flags.join(" ")
+        ));
+        let registers = self.registers.iter().enumerate().map(|(index, value)| format!("r{index} {value:3}"));
+        text.push_str(&format!("{}\n", registers.collect::<Vec<String>>().join("  ")));
+
+        let mut skipped = false;
+        for (row, chunk) in self.memory.chunks(16).enumerate() {
+            if chunk.iter().all(|byte| *byte == 0) {
+                if !skipped {
+                    text.push_str("*\n");
+                }
+                skipped = true;
+                continue;
+            }
+            skipped = false;
+            let bytes = chunk.iter().map(|byte| format!("{byte:02x}")).collect::<Vec<String>>();
+            text.push_str(&format!("{:#06x}  {}\n", row * 16, bytes.join(" ")));
+        }
+        text
+    }
+
+    fn json(&self) -> String {
+        let list = |values: &[Data]| values.iter().map(Data::to_string).collect::<Vec<String>>().join(",");
+        let flags = self.flags.iter().map(|(name, set)| format!("\"{name}\":{set}")).collect::<Vec<String>>();
+        format!(
+            "{{\"status\":\"{}\",\"cycles\":{},\"instructions\":{},\"program_counter\":{},\"stack_pointer\":{},\"flags\":{{{}}},\
+             \"registers\":[{}],\"memory\":[{}]}}",
+            self.status.name(),
+            self.cycles,
+            self.instructions,
+            self.program_counter,
+            self.stack_pointer,
+            flags.join(","),
+            list(&self.registers),
+            list(&self.memory)
+        )
+    }
+}
+
+fn main() -> ExitCode {
+    let arguments = std::env::args().skip(1).collect::<Vec<String>>();
+    match arguments.first().map(String::as_str) {
+        | None => {
+            eprintln!("{USAGE}");
+            return ExitCode::from(1);
+        }
+        | Some("help" | "--help" | "-h") => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        | Some(_) => {}
+    }
+
+    let result = parse(&arguments).and_then(|(command, options)| match command {
+        | Command::Assemble => assemble(&options).map(|()| Status::Halted),
+        | Command::Run => run(&options),
+        | Command::Disassemble => disassemble(&options).map(|()| Status::Halted),
+    });
+    match result {
+        | Ok(status) => ExitCode::from(status.code()),
+        | Err(error) => {
+            eprintln!("error: {error}");
+            if let CliError::Usage(_) = error {
+                eprintln!("\n{USAGE}");
+            }
+            ExitCode::from(1)
+        }
+    }
+}
+
+fn parse(arguments: &[String]) -> Result<(Command, Options), CliError> {
+    let command = match arguments.first().map(String::as_str) {
+        | Some("assemble") => Command::Assemble,
+        | Some("run") => Command::Run,
+        | Some("disasm") => Command::Disassemble,
+        | Some(other) => return Err(CliError::Usage(format!("unknown command `{other}`"))),
+        | None => return Err(CliError::Usage("missing command".into())),
+    };
+
+    let mut options = Options::default();
+    let mut arguments = arguments[1..].iter();
+    while let Some(argument) = arguments.next() {
+        if !argument.starts_with('-') {
+            options.inputs.push(argument.into());
+            continue;
+        }
+        if !command.accepts(argument) {
+            return Err(CliError::Usage(format!("unknown option `{argument}`")));
+        }
+
+        let mut value = || arguments.next().ok_or_else(|| CliError::Usage(format!("`{argument}` needs a value")));
+        match argument.as_str() {
+            | "-o" => options.output = Some(value()?.into()),
+            | "--map" => options.map = Some(value()?.into()),
+            | "--base" => options.layout.code = number(argument, value()?)?,
+            | "--data" => options.layout.data = Some(number(argument, value()?)?),
+            | "--bss" => options.layout.bss = Some(number(argument, value()?)?),
+            | "--config" => options.config = Some(value()?.into()),
+            | "--ram" => options.settings.push(("ram", value()?.clone())),
+            | "--registers" => options.settings.push(("registers", value()?.clone())),
+            | "--pc" => options.settings.push(("pc", value()?.clone())),
+            | "--sp" => options.settings.push(("sp", value()?.clone())),
+            | "--cycles" => options.settings.push(("cycles", value()?.clone())),
+            | "--dump" => {
+                options.dump = match value()?.as_str() {
+                    | "text" => Dump::Text,
+                    | "json" => Dump::Json,
+                    | "none" => Dump::None,
+                    | other => return Err(CliError::Usage(format!("unknown dump format `{other}`"))),
+                }
+            }
+            | "--object" => options.object = true,
+            | "--no-symbols" => options.symbols = false,
+            | "--trace" => options.trace = true,
+            | _ => unreachable!("every accepted option is handled"),
+        }
+    }
+
+    if options.inputs.is_empty() {
+        return Err(CliError::Usage("no input files".into()));
+    }
+    Ok((command, options))
+}
+
+fn number(option: &str, text: &str) -> Result<usize, CliError> {
+    number::parse(text).ok_or_else(|| CliError::Usage(format!("`{option}` expects a number, found `{text}`")))
+}
+
+/// the config file, or the default machine, with every setting flag applied on top
+fn machine(options: &Options) -> Result<MachineConfig, CliError> {
+    let mut config = match &options.config {
+        | Some(path) => MachineConfig::parse(&read_text(path)?)
+            .map_err(|error| CliError::Config { path: Some(path.into()), error })?,
+        | None => MachineConfig::default(),
+    };
+    for (key, value) in &options.settings {
+        config.set(key, value).map_err(|_| CliError::Usage(format!("`--{key}` expects a number, found `{value}`")))?;
+    }
+    config.validate().map_err(|error| CliError::Config { path: None, error })?;
+    Ok(config)
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, CliError> {
+    std::fs::read(path).map_err(|error| CliError::Io { path: path.into(), error })
+}
+
+fn read_text(path: &Path) -> Result<String, CliError> {
+    std::fs::read_to_string(path).map_err(|error| CliError::Io { path: path.into(), error })
+}
+
+fn write(path: &Path, contents: impl AsRef<[u8]>) -> Result<(), CliError> {
+    std::fs::write(path, contents).map_err(|error| CliError::Io { path: path.into(), error })
+}
+
+/// `.obj` inputs are read as object modules, everything else is assembled
+fn object(path: &Path, registers: usize) -> Result<Object, CliError> {
+    let text = read_text(path)?;
+    match path.extension().is_some_and(|extension| extension == "obj") {
+        | true => Object::parse(&text).map_err(|error| CliError::Object { path: path.into(), error }),
+        | false => assemble_object(&text, registers).map_err(|error| CliError::Assembly { path: path.into(), error }),
+    }
+}
+
+fn link(options: &Options, registers: usize) -> Result<Program, CliError> {
+    let mut linker = Linker::build(options.layout);
+    for path in &options.inputs {
+        let name = path.file_stem().map_or_else(|| path.display().to_string(), |stem| stem.to_string_lossy().into());
+        linker.add(name, object(path, registers)?);
+    }
+    linker.link().map_err(CliError::Link)
+}
+
+fn assemble(options: &Options) -> Result<(), CliError> {
+    let Some(output) = &options.output
+    else {
+        return Err(CliError::Usage("missing `-o <output>`".into()));
+    };
+    let config = machine(options)?;
+
+    if options.object {
+        let [input] = options.inputs.as_slice()
+        else {
+            return Err(CliError::Usage("`--object` takes exactly one input".into()));
+        };
+        return write(output, object(input, config.registers)?.export());
+    }
+
+    let program = link(options, config.registers)?;
+    let mut image = Image::from_program(&program, config.initial_stack_pointer()).map_err(CliError::Image)?;
+    if options.symbols {
+        image = image.with_symbols(program.symbols.clone());
+    }
+    write(output, image.to_bytes().map_err(CliError::Image)?)?;
+    if let Some(map) = &options.map {
+        write(map, program.symbols.export())?;
+    }
+    Ok(())
+}
+
+fn run(options: &Options) -> Result<Status, CliError> {
+    let config = machine(options)?;
+    let bytes = match options.inputs.as_slice() {
+        | [input] => read(input)?,
+        | _ => Vec::new(),
+    };
+    let image = match bytes.starts_with(IMAGE_MAGIC) {
+        | true => Image::parse(&bytes).map_err(CliError::Image)?,
+        | false => {
+            let program = link(options, config.registers)?;
+            Image::from_program(&program, config.initial_stack_pointer())
+                .map_err(CliError::Image)?
+                .with_symbols(program.symbols)
+        }
+    };
+
+    let snapshot = execute(&image, &config, options.trace)?;
+    match options.dump {
+        | Dump::Text => print!("{}", snapshot.text()),
+        | Dump::Json => println!("{}", snapshot.json()),
+        | Dump::None => {}
+    }
+    Ok(snapshot.status)
+}
+
+/// runs until halt, a fault or the cycle limit
+fn execute(image: &Image, config: &MachineConfig, trace: bool) -> Result<Snapshot, CliError> {
+    let mut machine = Machine::new(config.clone()).map_err(CliError::Map)?;
+    machine.load(image).map_err(CliError::Image)?;
+
+    let outcome = match trace {
+        | true => machine.run_until(|machine| {
+            println!("\x1b[2J\x1b[0H{machine:?}");
+            std::thread::sleep(std::time::Duration::from_millis(25));
+            false
+        }),
+        | false => machine.run(),
+    };
+    let status = match outcome.stop {
+        | Stop::Fault(fault) => {
+            eprintln!("fault: {fault}");
+            let symbols = image.symbols.clone().unwrap_or_default();
+            for address in machine.backtrace() {
+                let location = match symbols.locate(address as usize) {
+                    | Some((name, 0)) => format!(" {name}"),
+                    | Some((name, offset)) => format!(" {name}+{offset}"),
+                    | None => String::new(),
+                };
+                eprintln!("    at {address:#06x}{location}");
+            }
+            Status::Fault
+        }
+        | Stop::Halted => Status::Halted,
+        | Stop::Exited(code) => Status::Exited(code),
+        | Stop::CycleLimitReached => Status::CycleLimit,
+        | Stop::Breakpoint(_) | Stop::ExternalStop => unreachable!("runs from the command line set neither"),
+    };
+    Ok(Snapshot {
+        status,
+        cycles: outcome.cycles,
+        instructions: outcome.instructions,
+        program_counter: machine.cpu.program_counter,
+        stack_pointer: machine.cpu.stack_pointer,
+        flags: [
+            ("zero", machine.cpu.flags.zero),
+            ("less", machine.cpu.flags.less),
+            ("great", machine.cpu.flags.great),
+            ("carry", machine.cpu.flags.carry),
+            ("overflow", machine.cpu.flags.overflow),
+            ("negative", machine.cpu.flags.negative),
+        ],
+        registers: (0..config.registers).map(|index| machine.cpu.registers.read(index)).collect(),
+        memory: (0..machine.ram.size()).map(|address| machine.ram.read(address)).collect(),
+    })
+}
+
+fn disassemble(options: &Options) -> Result<(), CliError> {
+    let [input] = options.inputs.as_slice()
+    else {
+        return Err(CliError::Usage("`disasm` takes exactly one image".into()));
+    };
+    let image = Image::parse(&read(input)?).map_err(CliError::Image)?;
+    let symbols = match &options.map {
+        | Some(path) => {
+            SymbolTable::parse(&read_text(path)?).map_err(|error| CliError::Symbols { path: path.into(), error })?
+        }
+        | None => image.symbols.clone().unwrap_or_default(),
+    };
+
+    let mut memory = MemoryBlock::<Data>::new(ADDRESS_SPACE);
+    for segment in &image.segments {
+        for (address, byte) in (segment.address..).zip(&segment.bytes) {
+            *memory.write(address) = *byte;
+        }
+    }
+
+    let disassembler = Disassembler::build(&memory).with_symbols(&symbols);
+    println!("; entry {:#06x}, stack {:#06x}", image.entry, image.stack_pointer);
+    for segment in &image.segments {
+        let range = segment.address..segment.address + segment.bytes.len();
+        println!("\n; {} {:#06x}..{:#06x}", segment.section.name(), range.start, range.end);
+        match segment.section {
+            | Section::Code => disassembler.listing(range).iter().for_each(|line| println!("{line}")),
+            | Section::Data | Section::Bss => {
+                for (index, chunk) in segment.bytes.chunks(8).enumerate() {
+                    let bytes = chunk.iter().map(|byte| format!("{byte:#04x}")).collect::<Vec<String>>();
+                    println!("{:#06x}  .byte {}", range.start + index * 8, bytes.join(", "));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arguments(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    fn image(source: &str) -> Image {
+        let mut linker = Linker::build(Layout::default());
+        linker.add("test", assemble_object(source, 8).unwrap());
+        Image::from_program(&linker.link().unwrap(), 255).unwrap()
+    }
+
+    #[test]
+    fn parsed_options() {
+        let (command, options) = parse(&arguments("run a.asm b.obj --ram 0x400 --cycles 50 --dump json")).unwrap();
+        assert!(command == Command::Run);
+        assert!(options.inputs == [PathBuf::from("a.asm"), PathBuf::from("b.obj")]);
+        assert!(options.dump == Dump::Json);
+        let config = machine(&options).unwrap();
+        assert!(config.ram_size == 1024 && config.cycle_limit == 50 && config.initial_stack_pointer() == 0x3FF);
+
+        let (_, options) = parse(&arguments("assemble a.asm -o a.img --base 0x10 --ram 0x100 --no-symbols")).unwrap();
+        assert!(options.layout.code == 0x10 && !options.symbols);
+        assert!(machine(&options).unwrap().initial_stack_pointer() == 0xFF);
+    }
+
+    #[test]
+    fn rejected_arguments() {
+        let usage = |line: &str| matches!(parse(&arguments(line)), Err(CliError::Usage(_)));
+        assert!(usage("build a.asm"));
+        assert!(usage("disasm a.img --ram 256"));
+        assert!(usage("run a.asm --cycles"));
+        assert!(usage("run a.asm --dump yaml"));
+        assert!(usage("run --trace"));
+        assert!(usage("assemble a.asm --stack 0xff") && usage("assemble a.asm --cycles 5"));
+
+        let (_, options) = parse(&arguments("run a.asm --ram 0x100 --sp 0x100")).unwrap();
+        assert!(matches!(machine(&options), Err(CliError::Config { path: None, .. })));
+        let (_, options) = parse(&arguments("run a.asm --registers many")).unwrap();
+        assert!(matches!(machine(&options), Err(CliError::Usage(_))));
+    }
+
+    #[test]
+    fn exit_statuses() {
+        let config = MachineConfig { ram_size: 256, cycle_limit: 1000, ..Default::default() };
+        let halted = execute(&image("LOADIMM r2, 7\nHALT"), &config, false).unwrap();
+        assert!(halted.status == Status::Halted && halted.registers[2] == 7);
+
+        let looping = execute(&image("top: JUMP top"), &config, false).unwrap();
+        assert!(looping.status == Status::CycleLimit && looping.cycles == 1000);
+
+        let divide = execute(&image("LOADIMM r1, 0\nDIV r0, r0, r1"), &config, false).unwrap();
+        assert!(divide.status == Status::Fault);
+    }
+
+    #[test]
+    fn dump_formats() {
+        let config = MachineConfig { ram_size: 256, cycle_limit: 1000, ..Default::default() };
+        let snapshot = execute(&image("LOADIMM r0, 3\nHALT"), &config, false).unwrap();
+        let text = snapshot.text();
+        assert!(text.starts_with("halted after"));
+        assert!(text.lines().nth(2).is_some_and(|line| line.starts_with("r0   3  r1   0")));
+        assert!(text.lines().nth(3) == Some("0x0000  03 00 03 01 00 00 00 00 00 00 00 00 00 00 00 00"));
+        assert!(text.lines().nth(4) == Some("*"));
+
+        let json = snapshot.json();
+        assert!(json.starts_with("{\"status\":\"halted\","));
+        assert!(json.contains("\"registers\":[3,0,0,0,0,0,0,0]"));
+    }
 }
@@ -8,13 +8,20 @@ pub trait Addressable<Address> {
     fn read(&self, address: Address) -> Self::Data;
 
     fn write(&mut self, address: Address) -> &mut Self::Data;
+
+    /// like `read` but `None` past the end instead of panicking
+    fn try_read(&self, address: Address) -> Option<Self::Data>;
+
+    fn try_write(&mut self, address: Address) -> Option<&mut Self::Data>;
+
+    fn size(&self) -> usize;
 }
 
-pub struct MemoryBlock<const M: usize, Data> {
-    memory: [Data; M],
+pub struct MemoryBlock<Data> {
+    memory: Vec<Data>,
 }
 
-impl<const M: usize, Data> std::fmt::Debug for MemoryBlock<M, Data>
+impl<Data> std::fmt::Debug for MemoryBlock<Data>
 where
     Data: std::fmt::Debug,
 {
@@ -31,7 +38,7 @@ where
     }
 }
 
-impl<const M: usize, Data> std::fmt::Display for MemoryBlock<M, Data>
+impl<Data> std::fmt::Display for MemoryBlock<Data>
 where
     Data: std::fmt::Debug,
 {
@@ -44,16 +51,20 @@ where
     }
 }
 
-impl<const M: usize, Data> Default for MemoryBlock<M, Data>
+impl<Data> MemoryBlock<Data>
 where
     Data: Default + Copy,
 {
-    fn default() -> Self {
-        Self { memory: [Default::default(); M] }
+    pub fn new(size: usize) -> Self {
+        Self { memory: vec![Default::default(); size] }
+    }
+
+    pub fn size(&self) -> usize {
+        self.memory.len()
     }
 }
 
-impl<const M: usize, Data, Address> Addressable<Address> for MemoryBlock<M, Data>
+impl<Data, Address> Addressable<Address> for MemoryBlock<Data>
 where
     Address: Into<usize>,
     Data: Copy,
@@ -67,11 +78,23 @@ where
     fn write(&mut self, address: Address) -> &mut Self::Data {
         &mut self.memory[address.into()]
     }
+
+    fn try_read(&self, address: Address) -> Option<Self::Data> {
+        self.memory.get(address.into()).copied()
+    }
+
+    fn try_write(&mut self, address: Address) -> Option<&mut Self::Data> {
+        self.memory.get_mut(address.into())
+    }
+
+    fn size(&self) -> usize {
+        self.memory.len()
+    }
 }
 
-impl<const M: usize, Address, Data> Cycle<Address, Data> for MemoryBlock<M, Data>
+impl<Address, Data> Cycle<Address, Data> for MemoryBlock<Data>
 where
-    Address: Into<usize>,
+    Address: Into<usize> + Copy,
     Data: Copy,
 {
     fn cycle(&mut self, bus: &mut Bus<Address, Data>) {
@@ -81,8 +104,10 @@ where
                 assert!(bus_state.address.is_some());
                 assert!(bus_state.data.is_none());
                 if let Some(address) = bus_state.address.take() {
-                    let read_back = self.read(address);
-                    *bus_state.data = Some(read_back);
+                    match self.memory.get(address.into()) {
+                        | Some(read_back) => *bus_state.data = Some(*read_back),
+                        | None => *bus_state.error = Some(address),
+                    }
                 }
             }
             | BusState::Write => {
@@ -90,7 +115,10 @@ where
                 assert!(bus_state.address.is_some());
                 assert!(bus_state.data.is_some());
                 if let (Some(address), Some(data)) = (bus_state.address.take(), bus_state.data.take()) {
-                    *self.write(address) = data;
+                    match self.memory.get_mut(address.into()) {
+                        | Some(cell) => *cell = data,
+                        | None => *bus_state.error = Some(address),
+                    }
                 }
             }
             | BusState::Null => {}
@@ -104,15 +132,22 @@ mod tests {
 
     #[test]
     fn mem_read() {
-        let mut mem = MemoryBlock::<8, u8>::default();
+        let mut mem = MemoryBlock::<u8>::new(8);
         mem.memory[5] = 33;
         assert!(mem.read(5_u16) == 33);
     }
 
     #[test]
     fn mem_write() {
-        let mut mem = MemoryBlock::<8, u8>::default();
+        let mut mem = MemoryBlock::<u8>::new(8);
         *mem.write(5_u16) = 33;
         assert!(mem.memory[5] == 33);
     }
+
+    #[test]
+    fn mem_checked() {
+        let mut mem = MemoryBlock::<u8>::new(8);
+        *mem.try_write(7_u16).unwrap() = 33;
+        assert!(mem.try_read(7_u16) == Some(33) && mem.try_read(8_u16).is_none() && mem.try_write(8_u16).is_none());
+    }
 }
@@ -0,0 +1,19 @@
+/// reads a decimal or `0x` prefixed hexadecimal literal from a text format, `None` when it does not fit in `T`
+pub fn parse<T: TryFrom<i128>>(text: &str) -> Option<T> {
+    let value = match text.strip_prefix("0x") {
+        | Some(digits) => i128::from_str_radix(digits, 16).ok()?,
+        | None => text.parse().ok()?,
+    };
+    T::try_from(value).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn literals() {
+        assert!(parse::<usize>("0x1F") == Some(31) && parse::<i64>("-3") == Some(-3));
+        assert!(parse::<u8>("256").is_none() && parse::<usize>("-1").is_none() && parse::<usize>("0xg").is_none());
+    }
+}
@@ -0,0 +1,276 @@
+use crate::cpu::ADDRESS_SPACE;
+use crate::cpu::Data;
+use crate::number;
+
+/// first line of an exported object module, checked again on load
+const OBJECT_HEADER: &str = "; object v1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Section {
+    Code,
+    Data,
+    Bss,
+}
+
+impl Section {
+    pub const ALL: [Section; 3] = [Section::Code, Section::Data, Section::Bss];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            | Section::Code => "code",
+            | Section::Data => "data",
+            | Section::Bss => "bss",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Section> {
+        Self::ALL.into_iter().find(|section| section.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelocationKind {
+    Byte,
+    Low,
+    High,
+}
+
+impl RelocationKind {
+    const ALL: [RelocationKind; 3] = [RelocationKind::Byte, RelocationKind::Low, RelocationKind::High];
+
+    fn name(&self) -> &'static str {
+        match self {
+            | RelocationKind::Byte => "byte",
+            | RelocationKind::Low => "low",
+            | RelocationKind::High => "high",
+        }
+    }
+}
+
+/// what a relocation is measured from, either a section of the same module or a symbol exported by another
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    Section(Section),
+    External(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relocation {
+    pub section: Section,
+    pub offset: usize,
+    pub kind: RelocationKind,
+    pub target: Target,
+    pub addend: i64,
+}
+
+impl Relocation {
+    /// the patched byte once the target sits at `base`, or the address that does not fit in it
+    pub fn resolve(&self, base: usize) -> Result<Data, i64> {
+        let address = self.addend + base as i64;
+        match self.kind {
+            | RelocationKind::Byte => Data::try_from(address).map_err(|_| address),
+            | RelocationKind::Low => Ok((address & 0xFF) as Data),
+            | RelocationKind::High => Ok(((address >> 8) & 0xFF) as Data),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectSymbol {
+    pub name: String,
+    pub section: Section,
+    pub offset: usize,
+    pub global: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectError {
+    pub line: usize,
+    pub text: String,
+}
+
+impl std::fmt::Display for ObjectError {
+    fn fmt(&self, frmtr: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(frmtr, "object line {}: cannot read `{}`", self.line, self.text)
+    }
+}
+
+impl std::error::Error for ObjectError {}
+
+/// one assembled module, bss is kept zero filled here but only its size is exported
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Object {
+    pub sections: [Vec<Data>; 3],
+    pub symbols: Vec<ObjectSymbol>,
+    pub externals: Vec<String>,
+    pub relocations: Vec<Relocation>,
+}
+
+impl Object {
+    pub fn section(&self, section: Section) -> &[Data] {
+        &self.sections[section as usize]
+    }
+
+    pub fn section_mut(&mut self, section: Section) -> &mut Vec<Data> {
+        &mut self.sections[section as usize]
+    }
+
+    pub fn export(&self) -> String {
+        let mut text = format!("{OBJECT_HEADER}\n");
+        for section in [Section::Code, Section::Data] {
+            let bytes = self.section(section).iter().map(|byte| format!(" {byte:02x}")).collect::<String>();
+            text.push_str(&format!("{}{bytes}\n", section.name()));
+        }
+        text.push_str(&format!("bss {}\n", self.section(Section::Bss).len()));
+        for symbol in &self.symbols {
+            let binding = if symbol.global { "global" } else { "local" };
+            text.push_str(&format!(
+                "symbol {} {} {:#06x} {binding}\n",
+                symbol.name,
+                symbol.section.name(),
+                symbol.offset
+            ));
+        }
+        for name in &self.externals {
+            text.push_str(&format!("extern {name}\n"));
+        }
+        for relocation in &self.relocations {
+            let target = match &relocation.target {
+                | Target::Section(section) => format!("section {}", section.name()),
+                | Target::External(name) => format!("extern {name}"),
+            };
+            text.push_str(&format!(
+                "reloc {} {:#06x} {} {target} {}\n",
+                relocation.section.name(),
+                relocation.offset,
+                relocation.kind.name(),
+                relocation.addend
+            ));
+        }
+        text
+    }
+
+    /// sections are capped at the address space and every symbol and relocation must land inside its section
+    pub fn parse(text: &str) -> Result<Self, ObjectError> {
+        let mut lines = text.lines().enumerate().map(|(index, text)| (index + 1, text.trim()));
+        if lines.next().is_none_or(|(_, header)| header != OBJECT_HEADER) {
+            return Err(ObjectError { line: 1, text: text.lines().next().unwrap_or_default().into() });
+        }
+
+        let mut object = Self::default();
+        // checked once every section is known, a symbol may sit one past the end of its section, a relocation may not
+        let mut references = Vec::new();
+        for (line, text) in lines.filter(|(_, text)| !text.is_empty()) {
+            let error = || ObjectError { line, text: text.into() };
+            let words = text.split_whitespace().collect::<Vec<&str>>();
+            match words.as_slice() {
+                | [section @ ("code" | "data"), bytes @ ..] => {
+                    let bytes =
+                        bytes.iter().map(|byte| Data::from_str_radix(byte, 16).ok()).collect::<Option<Vec<_>>>();
+                    let bytes = bytes.filter(|bytes| bytes.len() <= ADDRESS_SPACE).ok_or_else(error)?;
+                    *object.section_mut(Section::from_name(section).ok_or_else(error)?) = bytes;
+                }
+                | ["bss", size] => {
+                    let size = number::parse(size).filter(|size| *size <= ADDRESS_SPACE).ok_or_else(error)?;
+                    *object.section_mut(Section::Bss) = vec![Default::default(); size];
+                }
+                | ["symbol", name, section, offset, binding @ ("global" | "local")] => {
+                    let symbol = ObjectSymbol {
+                        name: name.to_string(),
+                        section: Section::from_name(section).ok_or_else(error)?,
+                        offset: number::parse(offset).ok_or_else(error)?,
+                        global: *binding == "global",
+                    };
+                    references.push((line, text, symbol.section, symbol.offset, true));
+                    object.symbols.push(symbol);
+                }
+                | ["extern", name] => object.externals.push(name.to_string()),
+                | ["reloc", section, offset, kind, target_kind, target, addend] => {
+                    let target = match *target_kind {
+                        | "section" => Target::Section(Section::from_name(target).ok_or_else(error)?),
+                        | "extern" => Target::External(target.to_string()),
+                        | _ => return Err(error()),
+                    };
+                    let relocation = Relocation {
+                        section: Section::from_name(section).ok_or_else(error)?,
+                        offset: number::parse(offset).ok_or_else(error)?,
+                        kind: RelocationKind::ALL
+                            .into_iter()
+                            .find(|candidate| candidate.name() == *kind)
+                            .ok_or_else(error)?,
+                        target,
+                        addend: number::parse(addend).ok_or_else(error)?,
+                    };
+                    references.push((line, text, relocation.section, relocation.offset, false));
+                    object.relocations.push(relocation);
+                }
+                | _ => return Err(error()),
+            }
+        }
+        for (line, text, section, offset, at_end) in references {
+            let length = object.section(section).len();
+            if offset > length || (offset == length && !at_end) {
+                return Err(ObjectError { line, text: text.into() });
+            }
+        }
+        Ok(object)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object() -> Object {
+        Object {
+            sections: [vec![0x0a, 0x00, 0x03], vec![b'h', 0], vec![0; 4]],
+            symbols: vec![ObjectSymbol { name: "main".into(), section: Section::Code, offset: 0, global: true }],
+            externals: vec!["print".into()],
+            relocations: vec![
+                Relocation {
+                    section: Section::Code,
+                    offset: 1,
+                    kind: RelocationKind::Byte,
+                    target: Target::External("print".into()),
+                    addend: 0,
+                },
+                Relocation {
+                    section: Section::Code,
+                    offset: 2,
+                    kind: RelocationKind::Low,
+                    target: Target::Section(Section::Data),
+                    addend: -1,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn export_roundtrip() {
+        let object = object();
+        let text = object.export();
+        assert!(text.lines().nth(3) == Some("bss 4"));
+        assert!(text.lines().last() == Some("reloc code 0x0002 low section data -1"));
+        assert!(Object::parse(&text).unwrap() == object);
+    }
+
+    #[test]
+    fn malformed_object() {
+        let error = Object::parse("; object v1\ncode 0a\nreloc code 0x0001 word section data 0").unwrap_err();
+        assert!(error.line == 3);
+        assert!(Object::parse("code 0a").unwrap_err().line == 1);
+        let error = |text: &str| Object::parse(&format!("; object v1\ncode 0a 00\n{text}")).unwrap_err().line;
+        assert!(error("symbol end code 0x0003 local") == 3);
+        assert!(error("reloc code 0x0002 byte section data 0") == 3);
+        assert!(error("bss 0x10001") == 3);
+        assert!(Object::parse("; object v1\ncode 0a 00\nsymbol end code 0x0002 local").is_ok());
+    }
+
+    #[test]
+    fn resolve_ranges() {
+        let relocation = &object().relocations[1];
+        assert!(relocation.resolve(0x181) == Ok(0x80));
+        let byte = Relocation { kind: RelocationKind::Byte, ..relocation.clone() };
+        assert!(byte.resolve(0x101) == Err(0x100));
+    }
+}
@@ -0,0 +1,141 @@
+use crate::number;
+use std::collections::BTreeMap;
+
+/// first line of an exported symbol map, checked again on load
+const SYMBOLS_HEADER: &str = "; symbols v1";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolError {
+    pub line: usize,
+    pub text: String,
+}
+
+impl std::fmt::Display for SymbolError {
+    fn fmt(&self, frmtr: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(frmtr, "symbol map line {}: cannot read `{}`", self.line, self.text)
+    }
+}
+
+impl std::error::Error for SymbolError {}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SymbolTable {
+    symbols: BTreeMap<String, usize>,
+}
+
+impl SymbolTable {
+    pub fn insert(&mut self, name: impl Into<String>, address: usize) {
+        self.symbols.insert(name.into(), address);
+    }
+
+    pub fn name(&self, address: usize) -> Option<&str> {
+        self.symbols.iter().find(|(_, candidate)| **candidate == address).map(|(name, _)| name.as_str())
+    }
+
+    /// the closest symbol at or below `address` and how far past it `address` lies
+    pub fn locate(&self, address: usize) -> Option<(&str, usize)> {
+        self.symbols
+            .iter()
+            .filter(|(_, candidate)| **candidate <= address)
+            .max_by_key(|(_, candidate)| **candidate)
+            .map(|(name, candidate)| (name.as_str(), address - candidate))
+    }
+
+    pub fn address(&self, name: &str) -> Option<usize> {
+        self.symbols.get(name).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.symbols.iter().map(|(name, address)| (name.as_str(), *address))
+    }
+
+    pub fn merge(&mut self, other: &SymbolTable) {
+        self.symbols.extend(other.symbols.iter().map(|(name, address)| (name.clone(), *address)));
+    }
+
+    /// symbols ordered by address, the order both the map and the export are written in
+    fn by_address(&self) -> Vec<(&str, usize)> {
+        let mut symbols = self.symbols.iter().map(|(name, address)| (name.as_str(), *address)).collect::<Vec<_>>();
+        symbols.sort_by_key(|(name, address)| (*address, *name));
+        symbols
+    }
+
+    /// machine-readable map, one `name address` pair per line, that `parse` reads back
+    pub fn export(&self) -> String {
+        let mut text = format!("{SYMBOLS_HEADER}\n");
+        for (name, address) in self.by_address() {
+            text.push_str(&format!("{name} {address:#06x}\n"));
+        }
+        text
+    }
+
+    pub fn parse(text: &str) -> Result<Self, SymbolError> {
+        let mut lines = text.lines().enumerate().map(|(index, text)| (index + 1, text.trim()));
+        if lines.next().is_none_or(|(_, header)| header != SYMBOLS_HEADER) {
+            return Err(SymbolError { line: 1, text: text.lines().next().unwrap_or_default().into() });
+        }
+
+        let mut symbols = Self::default();
+        for (line, text) in lines.filter(|(_, text)| !text.is_empty()) {
+            let error = || SymbolError { line, text: text.into() };
+            let Some((name, address)) = text.split_once(char::is_whitespace)
+            else {
+                return Err(error());
+            };
+            symbols.insert(name, number::parse(address.trim()).ok_or_else(error)?);
+        }
+        Ok(symbols)
+    }
+}
+
+impl std::fmt::Display for SymbolTable {
+    fn fmt(&self, frmtr: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let width = self.symbols.keys().map(String::len).max().unwrap_or_default();
+        for (name, address) in self.by_address() {
+            writeln!(frmtr, "{name:<width$}  {address:#06x}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reverse_lookup() {
+        let mut symbols = SymbolTable::default();
+        symbols.insert("start", 0);
+        symbols.insert("loop", 16);
+        assert!(symbols.name(16) == Some("loop"));
+        assert!(symbols.name(3).is_none());
+    }
+
+    #[test]
+    fn nearest_symbol() {
+        let mut symbols = SymbolTable::default();
+        symbols.insert("main", 0x00);
+        symbols.insert("double", 0x06);
+        assert!(symbols.locate(0x08) == Some(("double", 2)));
+        assert!(symbols.locate(0x05) == Some(("main", 5)));
+        assert!(SymbolTable::default().locate(0x05).is_none());
+    }
+
+    #[test]
+    fn export_roundtrip() {
+        let mut symbols = SymbolTable::default();
+        symbols.insert("loop", 16);
+        symbols.insert("start", 0);
+        let text = symbols.export();
+        assert!(text == "; symbols v1\nstart 0x0000\nloop 0x0010\n");
+        assert!(SymbolTable::parse(&text).unwrap() == symbols);
+        assert!(symbols.to_string() == "start  0x0000\nloop   0x0010\n");
+    }
+
+    #[test]
+    fn malformed_map() {
+        assert!(SymbolTable::parse("start 0x0000").unwrap_err().line == 1);
+        let error = SymbolTable::parse("; symbols v1\n\nloop 0xzz\n").unwrap_err();
+        assert!(error == SymbolError { line: 3, text: "loop 0xzz".into() });
+    }
+}
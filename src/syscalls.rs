@@ -0,0 +1,169 @@
+use crate::cpu::Data;
+use crate::cpu::FaultKind;
+use crate::cpu::Pointer;
+use crate::devices::Console;
+use crate::devices::SharedConsole;
+use crate::memory::Addressable;
+use crate::memory::MemoryBlock;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::io::Write;
+
+/// the console set, arguments and results go through the low registers
+pub const EXIT: Data = 0;
+pub const PUT_CHAR: Data = 1;
+pub const GET_CHAR: Data = 2;
+pub const CYCLES: Data = 3;
+pub const PUT_STRING: Data = 4;
+
+/// what the processor does once the host has served a syscall
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyscallOutcome {
+    Continue,
+    Exit(Data),
+}
+
+/// the parts of the machine a host handler may touch while the processor waits on it
+pub struct SyscallContext<'d> {
+    pub registers: &'d mut MemoryBlock<Data>,
+    pub memory: &'d mut MemoryBlock<Data>,
+    pub cycles: usize,
+    pub instructions: usize,
+}
+
+impl SyscallContext<'_> {
+    /// a register the handler reads or writes, faulting like an instruction would when it does not exist
+    pub fn register(&mut self, index: usize) -> Result<&mut Data, FaultKind> {
+        self.registers.try_write(index).ok_or(FaultKind::InvalidRegister(index as Data))
+    }
+
+    pub fn read(&self, address: Pointer) -> Result<Data, FaultKind> {
+        self.memory.try_read(address as usize).ok_or(FaultKind::BusError(address))
+    }
+}
+
+pub type SyscallHandler = Box<dyn FnMut(&mut SyscallContext<'_>) -> Result<SyscallOutcome, FaultKind>>;
+
+/// host closures by syscall number, a number nobody registered faults
+#[derive(Default)]
+pub struct Syscalls {
+    handlers: BTreeMap<Data, SyscallHandler>,
+}
+
+impl std::fmt::Debug for Syscalls {
+    fn fmt(&self, frmtr: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        frmtr.debug_set().entries(self.handlers.keys()).finish()
+    }
+}
+
+impl Syscalls {
+    /// exit with the code in r0, put and get a character in r0, read the cycle count into r0 to r3 high byte
+    /// first, and print the zero terminated string the pair r0:r1 points at, a string with no end in memory faults
+    pub fn console(input: impl Read + 'static, output: impl Write + 'static) -> Self {
+        Self::shared(Console::new(input, output).shared())
+    }
+
+    /// the console set on a console the machine's port also uses
+    pub fn shared(console: SharedConsole) -> Self {
+        let mut syscalls = Self::default();
+        syscalls.register(EXIT, |context| Ok(SyscallOutcome::Exit(*context.register(0)?)));
+        let output = console.clone();
+        syscalls.register(PUT_CHAR, move |context| {
+            let byte = *context.register(0)?;
+            output.borrow_mut().put(&[byte]).map_err(|_| FaultKind::SyscallFailed(PUT_CHAR))?;
+            Ok(SyscallOutcome::Continue)
+        });
+        let input = console.clone();
+        syscalls.register(GET_CHAR, move |context| {
+            let byte = input.borrow_mut().get().map_err(|_| FaultKind::SyscallFailed(GET_CHAR))?;
+            *context.register(0)? = byte.unwrap_or_default();
+            *context.register(1)? = byte.is_none() as Data;
+            Ok(SyscallOutcome::Continue)
+        });
+        syscalls.register(CYCLES, |context| {
+            let cycles = (context.cycles as u32).to_be_bytes();
+            for (index, byte) in cycles.into_iter().enumerate() {
+                *context.register(index)? = byte;
+            }
+            Ok(SyscallOutcome::Continue)
+        });
+        syscalls.register(PUT_STRING, move |context| {
+            let mut address = Pointer::from_be_bytes([*context.register(0)?, *context.register(1)?]);
+            let mut text = Vec::new();
+            loop {
+                if text.len() == context.memory.size() {
+                    return Err(FaultKind::SyscallFailed(PUT_STRING));
+                }
+                match context.read(address)? {
+                    | 0 => break,
+                    | byte => text.push(byte),
+                }
+                address = address.wrapping_add(1);
+            }
+            console.borrow_mut().put(&text).map_err(|_| FaultKind::SyscallFailed(PUT_STRING))?;
+            Ok(SyscallOutcome::Continue)
+        });
+        syscalls
+    }
+
+    /// replaces whatever was registered under `number`
+    pub fn register(
+        &mut self,
+        number: Data,
+        handler: impl FnMut(&mut SyscallContext<'_>) -> Result<SyscallOutcome, FaultKind> + 'static,
+    ) {
+        self.handlers.insert(number, Box::new(handler));
+    }
+
+    pub fn dispatch(&mut self, number: Data, context: &mut SyscallContext<'_>) -> Result<SyscallOutcome, FaultKind> {
+        let Some(handler) = self.handlers.get_mut(&number)
+        else {
+            return Err(FaultKind::InvalidSyscall(number));
+        };
+        handler(context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::ADDRESS_SPACE;
+    use crate::testing::Capture;
+
+    #[test]
+    fn console_set() {
+        let capture = Capture::default();
+        let mut syscalls = Syscalls::console(&b"x"[..], capture.clone());
+        let (mut registers, mut memory) = (MemoryBlock::new(4), MemoryBlock::new(8));
+        *memory.write(5_usize) = b'o';
+        *memory.write(6_usize) = b'k';
+        let mut context =
+            SyscallContext { registers: &mut registers, memory: &mut memory, cycles: 0x1234, instructions: 0 };
+        let mut call = |number, context: &mut SyscallContext<'_>| syscalls.dispatch(number, context);
+
+        *context.register(0).unwrap() = b'>';
+        assert!(call(PUT_CHAR, &mut context) == Ok(SyscallOutcome::Continue));
+        *context.register(1).unwrap() = 5;
+        *context.register(0).unwrap() = 0;
+        call(PUT_STRING, &mut context).unwrap();
+        assert!(capture.0.borrow().as_slice() == b">ok");
+
+        call(GET_CHAR, &mut context).unwrap();
+        assert!(context.registers.read(0_usize) == b'x' && context.registers.read(1_usize) == 0);
+        call(GET_CHAR, &mut context).unwrap();
+        assert!(context.registers.read(1_usize) == 1);
+
+        call(CYCLES, &mut context).unwrap();
+        assert!((0..4_usize).map(|index| context.registers.read(index)).eq([0, 0, 0x12, 0x34]));
+        assert!(call(EXIT, &mut context) == Ok(SyscallOutcome::Exit(0)));
+        assert!(call(9, &mut context) == Err(FaultKind::InvalidSyscall(9)));
+
+        let mut memory = MemoryBlock::new(ADDRESS_SPACE);
+        for address in 0..ADDRESS_SPACE {
+            *memory.write(address) = b'.';
+        }
+        let mut unterminated =
+            SyscallContext { registers: &mut registers, memory: &mut memory, cycles: 0, instructions: 0 };
+        assert!(syscalls.dispatch(PUT_STRING, &mut unterminated) == Err(FaultKind::SyscallFailed(PUT_STRING)));
+    }
+}
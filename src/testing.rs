@@ -0,0 +1,17 @@
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+
+/// an output stream tests keep a handle to, every clone appends to the same bytes
+#[derive(Clone, Default)]
+pub struct Capture(pub Rc<RefCell<Vec<u8>>>);
+
+impl Write for Capture {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(bytes)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}